@@ -0,0 +1,21 @@
+use arsvt3d::MazeGenerator;
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// 地图尺寸由 MAP_WIDTH / MAP_HEIGHT 决定，这里比较两种挖路方式的耗时
+fn bench_generate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("maze_generate");
+    for (name, iterative) in [("recursive", false), ("iterative", true)] {
+        group.bench_function(name, |b| {
+            let mut rng = StdRng::seed_from_u64(42);
+            let mut generator = MazeGenerator::new();
+            generator.iterative = iterative;
+            b.iter(|| generator.generate(&mut rng));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_generate);
+criterion_main!(benches);
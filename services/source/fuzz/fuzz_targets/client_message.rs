@@ -0,0 +1,13 @@
+//! 把任意字节喂给 `ClientMessage::parse`，只允许返回 `Ok` 或 `Err`，不能 panic
+//!
+//! 运行：`cargo +nightly fuzz run client_message`，冒烟测试可以加 `-- -runs=5000`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use ttyd_rust::protocol::ClientMessage;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ClientMessage::parse(data);
+});
@@ -1,5 +1,6 @@
 use axum::{
     body::Body,
+    extract::Path,
     http::{header, HeaderValue, Response, StatusCode},
     response::IntoResponse,
 };
@@ -48,6 +49,14 @@ pub async fn static_handler(uri: &str) -> impl IntoResponse {
     }
 }
 
+pub async fn static_file_handler_path(Path(path): Path<String>) -> impl IntoResponse {
+    static_handler(&path).await
+}
+
+pub async fn static_file_handler_root() -> impl IntoResponse {
+    static_handler("/xterm.min.css").await
+}
+
 pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
 <html lang="en">
 <head>
@@ -155,6 +164,9 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
             var ws;
             var inputBuffer = [];
             var sendTimer = null;
+            var processExited = false;
+            // PTY 就绪或收到第一段输出前显示"连接中"提示
+            var connecting = true;
             
             try {
                 // 检查terminal元素是否存在
@@ -199,6 +211,14 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                     }
                 }
                 
+                term.write('\x1b[2mConnecting\u2026\x1b[0m');
+                var clearConnecting = function() {
+                    if (connecting) {
+                        connecting = false;
+                        term.write('\r\x1b[2K');
+                    }
+                };
+                
                 // 尝试加载WebGL插件（可选）
                 try {
                     if (typeof WebglAddon !== 'undefined' && WebglAddon.WebglAddon) {
@@ -213,7 +233,7 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                 
                 // 建立WebSocket连接
                 var protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
-                ws = new WebSocket(protocol + '//' + window.location.host + '/ws');
+                ws = new WebSocket(protocol + '//' + window.location.host + '/ws' + window.location.search);
                 ws.binaryType = 'arraybuffer';
                 
                 // 发送输入数据的函数
@@ -248,6 +268,14 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                 
                 // 处理终端输入
                 term.onData(function(data) {
+                    // 进程退出后按回车重新连接
+                    if (processExited) {
+                        if (data === '\r') {
+                            window.location.reload();
+                        }
+                        return;
+                    }
+                    
                     if (ws && ws.readyState === WebSocket.OPEN) {
                         inputBuffer.push(data);
                         
@@ -296,6 +324,7 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                         switch (cmd) {
                             case '0':
                                 // 终端输出
+                                clearConnecting();
                                 try {
                                     term.write(payload);
                                 } catch (e) {
@@ -314,6 +343,21 @@ pub const INDEX_HTML: &str = r#"<!DOCTYPE html>
                             case '2':
                                 // 忽略
                                 break;
+                            case '3':
+                                // PTY 已就绪
+                                clearConnecting();
+                                break;
+                            case '4':
+                                // 进程退出
+                                try {
+                                    var code = new TextDecoder().decode(payload);
+                                    processExited = true;
+                                    term.write('\r\n\x1b[33m[Process exited with code ' + code + ']\x1b[0m\r\n');
+                                    term.write('\x1b[33mPress Enter to reconnect\x1b[0m\r\n');
+                                } catch (e) {
+                                    console.error('Failed to handle process exit:', e);
+                                }
+                                break;
                             default:
                                 console.warn('Unknown command:', cmd);
                         }
@@ -0,0 +1,8 @@
+//! ttyd-rust 的库部分：嵌入方可以直接挂载 `router` 或用 `serve` 控制服务器生命周期
+
+pub mod http;
+pub mod protocol;
+mod pty;
+pub mod server;
+
+pub use server::{router, serve, serve_listener, AppState, Config, ConfigBuilder, NewlineMode};
@@ -1,29 +1,21 @@
-mod http;
-mod protocol;
-mod pty;
-mod server;
-
-use axum::{
-    extract::Path,
-    response::IntoResponse,
-    routing::get,
-    Router,
-};
 use clap::Parser;
-use http::static_handler;
-use server::{websocket::ws_handler, AppState, Config};
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tower_http::trace::TraceLayer;
 use tracing::info;
+use ttyd_rust::server::default_command;
+use ttyd_rust::{serve_listener, Config, NewlineMode};
 
 #[derive(Parser, Debug)]
 #[command(name = "ttyd-rust")]
 #[command(about = "Share your terminal over the web", long_about = None)]
 struct Args {
+    /// 端口为 0 时由系统分配空闲端口
     #[arg(short, long, default_value = "7681")]
     port: u16,
 
+    /// 绑定成功后把实际端口写入该文件
+    #[arg(long)]
+    port_file: Option<String>,
+
     #[arg(short, long, default_value = "0.0.0.0")]
     interface: String,
 
@@ -36,6 +28,10 @@ struct Args {
     #[arg(short = 'w', long)]
     cwd: Option<String>,
 
+    /// 允许已认证客户端在该目录下自选工作目录
+    #[arg(long, requires = "credential")]
+    cwd_root: Option<String>,
+
     #[arg(short = 'O', long)]
     check_origin: bool,
 
@@ -45,16 +41,37 @@ struct Args {
     #[arg(short, long)]
     once: bool,
 
-    #[arg(trailing_var_arg = true)]
-    command: Vec<String>,
-}
+    /// 合并输出的时间窗口（毫秒），0 表示不合并
+    #[arg(long, default_value = "5")]
+    coalesce_ms: u64,
 
-async fn static_file_handler_path(Path(path): Path<String>) -> impl IntoResponse {
-    static_handler(&path).await
-}
+    /// 把每个会话的输入输出追加写入该审计日志
+    #[arg(long)]
+    log_file: Option<String>,
+
+    /// 审计日志只记录输入
+    #[arg(long, requires = "log_file")]
+    log_input_only: bool,
+
+    /// 回车键写入 PTY 前的换行转换，auto 表示原样透传
+    #[arg(long, value_enum, default_value = "auto")]
+    crlf: NewlineMode,
+
+    /// 客户端单条消息的最大字节数，超过时断开连接
+    #[arg(long, default_value = "1048576")]
+    max_message_size: usize,
 
-async fn static_file_handler_root() -> impl IntoResponse {
-    static_handler("/xterm.min.css").await
+    /// 子进程的 LANG/LC_ALL（仅 Unix），传空字符串则沿用当前环境
+    #[arg(long, default_value = "C.UTF-8")]
+    locale: String,
+
+    /// 命令退出后自动重新启动（连接保持打开）
+    #[arg(long)]
+    restart: bool,
+
+    /// 要运行的命令，可使用 %USER%（认证用户名）和 %SESSION%（共享会话 id）占位符
+    #[arg(trailing_var_arg = true)]
+    command: Vec<String>,
 }
 
 #[tokio::main]
@@ -68,54 +85,47 @@ async fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
-    let config = Config {
-        port: args.port,
-        interface: args.interface.clone(),
-        command: if args.command.is_empty() {
-            #[cfg(unix)]
-            {
-                vec!["bash".to_string()]
-            }
-            #[cfg(windows)]
-            {
-                vec!["cmd.exe".to_string()]
-            }
-        } else {
-            args.command
-        },
-        cwd: args.cwd,
-        credential: args.credential,
-        writable: args.writable,
-        check_origin: args.check_origin,
-        max_clients: args.max_clients,
-        once: args.once,
+    let command = if args.command.is_empty() {
+        default_command()
+    } else {
+        args.command
     };
 
+    let config = Config::builder()
+        .port(args.port)
+        .interface(args.interface)
+        .command(command)
+        .cwd(args.cwd)
+        .cwd_root(args.cwd_root)
+        .credential(args.credential)
+        .writable(args.writable)
+        .check_origin(args.check_origin)
+        .max_clients(args.max_clients)
+        .once(args.once)
+        .coalesce_ms(args.coalesce_ms)
+        .log_file(args.log_file)
+        .log_input_only(args.log_input_only)
+        .newline(args.crlf)
+        .max_message_size(args.max_message_size)
+        .locale(args.locale)
+        .restart(args.restart)
+        .build()?;
+
     info!("Starting ttyd-rust server");
     info!("Command: {:?}", config.command);
-    info!("Port: {}", config.port);
     info!(
         "Writable: {}",
         if config.writable { "true" } else { "false" }
     );
 
-    let state = Arc::new(AppState::new(config.clone()));
-
-    let app = Router::new()
-        .route("/", get(http::index_handler))
-        .route("/token", get(http::token_handler))
-        .route("/ws", get(ws_handler))
-        .route("/js/*path", get(static_file_handler_path))
-        .route("/css/*path", get(static_file_handler_path))
-        .route("/xterm.min.css", get(static_file_handler_root))
-        .with_state(state)
-        .layer(TraceLayer::new_for_http());
-
     let addr: SocketAddr = format!("{}:{}", config.interface, config.port).parse()?;
-    info!("Server listening on http://{}", addr);
-
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
 
-    Ok(())
+    if let Some(port_file) = args.port_file {
+        let bound_addr = listener.local_addr()?;
+        std::fs::write(&port_file, bound_addr.port().to_string())?;
+        info!("Bound port written to {}", port_file);
+    }
+
+    serve_listener(listener, config, std::future::pending()).await
 }
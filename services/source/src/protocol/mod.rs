@@ -1,14 +1,29 @@
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
+// 客户端 -> 服务端，首字节为命令字符
+/// 终端输入，后跟原始输入字节
 pub const INPUT: char = '0';
+/// 调整终端尺寸，后跟 `{"columns":..,"rows":..}`
 pub const RESIZE_TERMINAL: char = '1';
+/// 暂停输出
 pub const PAUSE: char = '2';
+/// 恢复输出
 pub const RESUME: char = '3';
+/// 初始化消息，整条消息就是 JSON
 pub const JSON_DATA: char = '{';
 
+// 服务端 -> 客户端，首字节为命令字符
+/// 终端输出，后跟原始输出字节
 pub const OUTPUT: char = '0';
+/// 设置窗口标题，后跟 UTF-8 标题
 pub const SET_WINDOW_TITLE: char = '1';
+/// 设置客户端偏好，后跟 JSON
 pub const SET_PREFERENCES: char = '2';
+/// PTY 已就绪（或已加入共享会话），客户端可以清掉"连接中"提示，无负载
+pub const PTY_READY: char = '3';
+/// 进程已退出，后跟十进制退出码
+pub const PROCESS_EXIT: char = '4';
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitMessage {
@@ -18,6 +33,12 @@ pub struct InitMessage {
     pub rows: u16,
     #[serde(rename = "AuthToken")]
     pub auth_token: Option<String>,
+    /// 客户端请求的工作目录，相对于服务端配置的 cwd_root
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 用于替换命令模板中的 `%USER%`，仅在开启认证时采用
+    #[serde(default)]
+    pub user: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,17 +56,30 @@ pub enum ClientMessage {
     Init(InitMessage),
 }
 
+/// 客户端消息解析失败的原因，实现了 `std::error::Error`，可以直接用 `?` 转成 `anyhow::Error`
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Empty message")]
+    Empty,
+    #[error("Unknown command: {0}")]
+    UnknownCommand(char),
+    #[error("Invalid JSON payload: {0}")]
+    BadJson(#[from] serde_json::Error),
+}
+
 #[derive(Debug)]
 pub enum ServerMessage {
     Output(Vec<u8>),
     SetWindowTitle(String),
     SetPreferences(String),
+    Ready,
+    ProcessExit { code: i32 },
 }
 
 impl ClientMessage {
-    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
         if data.is_empty() {
-            anyhow::bail!("Empty message");
+            return Err(ParseError::Empty);
         }
 
         let cmd = data[0] as char;
@@ -66,7 +100,7 @@ impl ClientMessage {
                 let msg: InitMessage = serde_json::from_slice(data)?;
                 Ok(Self::Init(msg))
             }
-            _ => anyhow::bail!("Unknown command: {}", cmd),
+            _ => Err(ParseError::UnknownCommand(cmd)),
         }
     }
 }
@@ -89,6 +123,70 @@ impl ServerMessage {
                 msg.extend_from_slice(prefs.as_bytes());
                 msg
             }
+            Self::Ready => vec![PTY_READY as u8],
+            Self::ProcessExit { code } => {
+                let mut msg = vec![PROCESS_EXIT as u8];
+                msg.extend_from_slice(code.to_string().as_bytes());
+                msg
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_exit_is_the_command_byte_and_a_decimal_code() {
+        assert_eq!(ServerMessage::ProcessExit { code: 0 }.to_bytes(), b"40");
+        assert_eq!(ServerMessage::ProcessExit { code: 127 }.to_bytes(), b"4127");
+        assert_eq!(ServerMessage::ProcessExit { code: -1 }.to_bytes(), b"4-1");
+    }
+
+    #[test]
+    fn parse_errors_name_what_went_wrong() {
+        assert!(matches!(ClientMessage::parse(b""), Err(ParseError::Empty)));
+        assert!(matches!(ClientMessage::parse(b"9data"), Err(ParseError::UnknownCommand('9'))));
+        assert!(matches!(ClientMessage::parse(b"1{\"columns\":"), Err(ParseError::BadJson(_))));
+        assert!(matches!(ClientMessage::parse(b"{not json"), Err(ParseError::BadJson(_))));
+
+        // 可以直接用 ? 转成 anyhow::Error
+        let converted: anyhow::Error = ClientMessage::parse(b"").unwrap_err().into();
+        assert_eq!(converted.to_string(), "Empty message");
+    }
+
+    #[test]
+    fn parses_each_client_command() {
+        assert!(matches!(ClientMessage::parse(b"0ls\r"), Ok(ClientMessage::Input(input)) if input == "ls\r"));
+        assert!(matches!(
+            ClientMessage::parse(br#"1{"columns":120,"rows":40}"#),
+            Ok(ClientMessage::Resize { cols: 120, rows: 40 })
+        ));
+        assert!(matches!(ClientMessage::parse(b"2"), Ok(ClientMessage::Pause)));
+        assert!(matches!(ClientMessage::parse(b"3"), Ok(ClientMessage::Resume)));
+        assert!(matches!(
+            ClientMessage::parse(br#"{"columns":80,"rows":24,"AuthToken":"secret"}"#),
+            Ok(ClientMessage::Init(InitMessage { columns: 80, rows: 24, auth_token: Some(ref token), .. })) if token == "secret"
+        ));
+    }
+
+    #[test]
+    fn parse_never_panics_on_random_bytes() {
+        // 不引入随机数依赖，用 xorshift 生成可复现的输入
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let prefixes: [&[u8]; 6] = [b"", b"0", b"1", b"1{", b"{", b"{\"columns\":"];
+        for i in 0..5000 {
+            let mut data = prefixes[i % prefixes.len()].to_vec();
+            let len = (next() % 48) as usize;
+            data.extend((0..len).map(|_| next() as u8));
+            let _ = ClientMessage::parse(&data);
         }
     }
 }
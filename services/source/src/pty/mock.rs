@@ -0,0 +1,76 @@
+use super::PtySize;
+use anyhow::Result;
+use bytes::Bytes;
+use tokio::sync::{mpsc, watch};
+
+/// 不启动任何进程的内存 PTY：输入原样回显到输出，调整大小时输出一行提示。
+/// 用 `--features mock-pty` 编译，便于在没有 shell 的环境里调试 websocket 处理逻辑
+pub struct PtyProcessInner {
+    output_tx: mpsc::Sender<Bytes>,
+    exit_tx: watch::Sender<Option<i32>>,
+}
+
+impl PtyProcessInner {
+    pub async fn spawn(
+        _command: Vec<String>,
+        _size: PtySize,
+        _cwd: Option<String>,
+        _locale: String,
+        output_tx: mpsc::Sender<Bytes>,
+        mut input_rx: mpsc::UnboundedReceiver<Bytes>,
+        exit_tx: watch::Sender<Option<i32>>,
+    ) -> Result<Self> {
+        let echo_tx = output_tx.clone();
+        tokio::spawn(async move {
+            while let Some(data) = input_rx.recv().await {
+                if echo_tx.send(data).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            output_tx,
+            exit_tx,
+        })
+    }
+
+    pub fn pid(&self) -> u32 {
+        0
+    }
+
+    pub async fn resize(&self, size: PtySize) -> Result<()> {
+        let notice = format!("[resize {}x{}]\r\n", size.cols, size.rows);
+        let _ = self.output_tx.send(Bytes::from(notice)).await;
+        Ok(())
+    }
+
+    pub async fn kill(&self) -> Result<()> {
+        let _ = self.exit_tx.send(Some(0));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn echoes_input_and_reports_resizes() {
+        let (output_tx, mut output_rx) = mpsc::channel(8);
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let (exit_tx, exit_rx) = watch::channel(None);
+        let pty = PtyProcessInner::spawn(Vec::new(), PtySize::default(), None, String::new(), output_tx, input_rx, exit_tx)
+            .await
+            .unwrap();
+
+        input_tx.send(Bytes::from_static(b"hello")).unwrap();
+        assert_eq!(output_rx.recv().await.unwrap(), Bytes::from_static(b"hello"));
+
+        pty.resize(PtySize { cols: 100, rows: 30 }).await.unwrap();
+        assert_eq!(output_rx.recv().await.unwrap(), Bytes::from_static(b"[resize 100x30]\r\n"));
+
+        pty.kill().await.unwrap();
+        assert_eq!(*exit_rx.borrow(), Some(0));
+    }
+}
@@ -1,18 +1,26 @@
 use anyhow::{Context, Result};
 use bytes::Bytes;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
-#[cfg(unix)]
+#[cfg(feature = "mock-pty")]
+mod mock;
+#[cfg(all(unix, not(feature = "mock-pty")))]
 mod unix;
-#[cfg(windows)]
+#[cfg(all(windows, not(feature = "mock-pty")))]
 mod windows;
 
-#[cfg(unix)]
+#[cfg(feature = "mock-pty")]
+pub use mock::*;
+#[cfg(all(unix, not(feature = "mock-pty")))]
 pub use unix::*;
-#[cfg(windows)]
+#[cfg(all(windows, not(feature = "mock-pty")))]
 pub use windows::*;
 
+/// 输出通道容量（块数）。客户端发送变慢时通道会被填满，
+/// PTY 读取随之暂停，由子进程自己阻塞在写输出上，而不是在服务端无限缓冲
+const OUTPUT_CHANNEL_CAPACITY: usize = 64;
+
 #[derive(Debug, Clone)]
 pub struct PtySize {
     pub cols: u16,
@@ -28,10 +36,41 @@ impl Default for PtySize {
     }
 }
 
+/// 在 fork 之前检查命令是否存在，避免子进程 exec 失败后浏览器只看到空白终端。
+/// 带 `/` 的相对路径按子进程的工作目录 `cwd` 解析，和 exec 时一致
+#[cfg(unix)]
+pub(crate) fn command_exists(program: &str, cwd: Option<&str>) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+
+    if program.contains('/') {
+        return match cwd {
+            Some(dir) => is_executable(&Path::new(dir).join(program)),
+            None => is_executable(Path::new(program)),
+        };
+    }
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program))))
+        .unwrap_or(false)
+}
+
+// Windows 上命令解析还涉及 PATHEXT 和内置命令，交给 CreateProcessW 报错
+#[cfg(windows)]
+pub(crate) fn command_exists(_program: &str, _cwd: Option<&str>) -> bool {
+    true
+}
+
 pub struct PtyProcess {
     pub pid: u32,
     tx: mpsc::UnboundedSender<Bytes>,
     inner: Arc<PtyProcessInner>,
+    exit_rx: watch::Receiver<Option<i32>>,
 }
 
 impl PtyProcess {
@@ -39,11 +78,20 @@ impl PtyProcess {
         command: Vec<String>,
         size: PtySize,
         cwd: Option<String>,
-    ) -> Result<(Self, mpsc::UnboundedReceiver<Bytes>)> {
-        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        locale: String,
+    ) -> Result<(Self, mpsc::Receiver<Bytes>)> {
+        // 模拟后端不执行命令，无需检查
+        if let Some(program) = command.first().filter(|_| !cfg!(feature = "mock-pty")) {
+            if !command_exists(program, cwd.as_deref()) {
+                anyhow::bail!("command not found: {}", program);
+            }
+        }
+
+        let (output_tx, output_rx) = mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
         let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let (exit_tx, exit_rx) = watch::channel(None);
 
-        let inner = PtyProcessInner::spawn(command, size, cwd, output_tx, input_rx)
+        let inner = PtyProcessInner::spawn(command, size, cwd, locale, output_tx, input_rx, exit_tx)
             .await
             .context("Failed to spawn PTY process")?;
 
@@ -51,6 +99,7 @@ impl PtyProcess {
             pid: inner.pid(),
             tx: input_tx,
             inner: Arc::new(inner),
+            exit_rx,
         };
 
         Ok((process, output_rx))
@@ -67,7 +116,110 @@ impl PtyProcess {
         self.inner.resize(size).await
     }
 
+    /// 等待子进程退出并返回退出码；无法获知时返回 None
+    pub async fn wait(&self) -> Option<i32> {
+        let mut exit_rx = self.exit_rx.clone();
+        let code = *exit_rx.wait_for(|code| code.is_some()).await.ok()?;
+        code
+    }
+
     pub async fn kill(&self) -> Result<()> {
         self.inner.kill().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(unix, not(feature = "mock-pty")))]
+    #[test]
+    fn command_exists_checks_path_and_explicit_paths() {
+        assert!(command_exists("sh", None));
+        assert!(command_exists("/bin/sh", None));
+        assert!(!command_exists("ttyd-rust-no-such-command", None));
+        assert!(!command_exists("/nonexistent/ttyd-rust", None));
+        // 绝对路径不受 cwd 影响
+        assert!(command_exists("/bin/sh", Some("/nonexistent")));
+    }
+
+    #[cfg(all(unix, not(feature = "mock-pty")))]
+    #[test]
+    fn relative_paths_are_resolved_against_the_child_cwd() {
+        assert!(command_exists("./sh", Some("/bin")));
+        assert!(command_exists("bin/sh", Some("/")));
+        assert!(!command_exists("./sh", Some("/nonexistent")));
+    }
+
+    #[cfg(all(unix, not(feature = "mock-pty")))]
+    #[tokio::test]
+    async fn spawning_a_missing_command_fails_before_forking() {
+        let result = PtyProcess::spawn(
+            vec!["ttyd-rust-no-such-command".to_string()],
+            PtySize::default(),
+            None,
+            String::new(),
+        )
+        .await;
+        let err = result.err().expect("spawn should fail");
+        assert_eq!(err.to_string(), "command not found: ttyd-rust-no-such-command");
+    }
+
+    #[cfg(feature = "mock-pty")]
+    #[tokio::test]
+    async fn unread_output_stops_the_pty_instead_of_buffering() {
+        let (process, mut output_rx) = PtyProcess::spawn(vec!["mock".to_string()], PtySize::default(), None, String::new())
+            .await
+            .unwrap();
+        for _ in 0..OUTPUT_CHANNEL_CAPACITY * 4 {
+            process.write(Bytes::from_static(b"x")).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // 没人读输出时，通道里最多积压 OUTPUT_CHANNEL_CAPACITY 块，其余停在 PTY 一侧
+        let mut buffered = 0;
+        while output_rx.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, OUTPUT_CHANNEL_CAPACITY);
+
+        // 读走之后 PTY 继续产出
+        let next = tokio::time::timeout(std::time::Duration::from_secs(5), output_rx.recv()).await;
+        assert!(matches!(next, Ok(Some(_))));
+    }
+
+    #[cfg(all(unix, not(feature = "mock-pty")))]
+    #[tokio::test]
+    async fn ctrl_c_reaches_the_child_while_output_is_backed_up() {
+        let command = ["sh", "-c", "while :; do echo flooding the terminal; done"].map(String::from).to_vec();
+        let (process, _output_rx) = PtyProcess::spawn(command, PtySize::default(), None, String::new())
+            .await
+            .unwrap();
+        // 不读输出，等通道被填满、读取暂停
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        process.write(Bytes::from_static(b"\x03")).await.unwrap();
+        let code = tokio::time::timeout(std::time::Duration::from_secs(5), process.wait()).await;
+        assert_eq!(code.expect("Ctrl-C was not delivered"), Some(128 + 2));
+    }
+
+    #[cfg(all(unix, not(feature = "mock-pty")))]
+    #[tokio::test]
+    async fn locale_is_exported_to_the_child() {
+        let command = ["sh", "-c", "echo \"[$LANG|$LC_ALL]\""].map(String::from).to_vec();
+        let (_process, mut output_rx) = PtyProcess::spawn(command, PtySize::default(), None, "C.UTF-8".to_string())
+            .await
+            .unwrap();
+        let mut output = String::new();
+        let read = async {
+            while let Some(chunk) = output_rx.recv().await {
+                output.push_str(&String::from_utf8_lossy(&chunk));
+                if output.contains(']') {
+                    break;
+                }
+            }
+        };
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(5), read).await;
+        assert!(output.contains("[C.UTF-8|C.UTF-8]"), "output was {:?}", output);
+    }
+}
@@ -3,13 +3,13 @@ use anyhow::{Context, Result};
 use bytes::Bytes;
 use nix::pty::{forkpty, Winsize};
 use nix::sys::signal::{kill, Signal};
-use nix::sys::wait::waitpid;
+use nix::sys::wait::{waitpid, WaitStatus};
 use nix::unistd::{chdir, execvp, ForkResult, Pid};
 use std::env;
 use std::ffi::CString;
 use std::os::fd::{AsRawFd, RawFd};
 use std::os::unix::io::IntoRawFd;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio::task;
 use std::os::unix::io::FromRawFd;
 
@@ -23,8 +23,10 @@ impl PtyProcessInner {
         command: Vec<String>,
         size: PtySize,
         cwd: Option<String>,
-        output_tx: mpsc::UnboundedSender<Bytes>,
+        locale: String,
+        output_tx: mpsc::Sender<Bytes>,
         mut input_rx: mpsc::UnboundedReceiver<Bytes>,
+        exit_tx: watch::Sender<Option<i32>>,
     ) -> Result<Self> {
         let winsize = Winsize {
             ws_row: size.rows,
@@ -50,15 +52,32 @@ impl PtyProcessInner {
                     let async_fd = AsyncFd::new(master_fd_raw).unwrap();
                     
                     let mut buffer = vec![0u8; 8192];
-                    loop {
+                    'io: loop {
                         tokio::select! {
                             Ok(mut guard) = async_fd.readable() => {
                                 match master_file.read(&mut buffer) {
                                     Ok(0) => break,
                                     Ok(n) => {
                                         guard.clear_ready();
-                                        if output_tx.send(Bytes::copy_from_slice(&buffer[..n])).is_err() {
-                                            break;
+                                        // 输出通道满时停止读取，但输入照常写入 PTY，
+                                        // 否则客户端变慢时 Ctrl-C 之类的按键也会被卡住
+                                        let send = output_tx.send(Bytes::copy_from_slice(&buffer[..n]));
+                                        tokio::pin!(send);
+                                        loop {
+                                            tokio::select! {
+                                                result = &mut send => {
+                                                    if result.is_err() {
+                                                        break 'io;
+                                                    }
+                                                    break;
+                                                }
+                                                Some(data) = input_rx.recv() => {
+                                                    if let Err(e) = master_file.write_all(&data) {
+                                                        eprintln!("PTY write error: {}", e);
+                                                        break 'io;
+                                                    }
+                                                }
+                                            }
                                         }
                                     }
                                     Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -80,8 +99,13 @@ impl PtyProcessInner {
                     }
                 });
 
-                task::spawn(async move {
-                    let _ = waitpid(child, None);
+                task::spawn_blocking(move || {
+                    let code = match waitpid(child, None) {
+                        Ok(WaitStatus::Exited(_, code)) => code,
+                        Ok(WaitStatus::Signaled(_, signal, _)) => 128 + signal as i32,
+                        _ => -1,
+                    };
+                    let _ = exit_tx.send(Some(code));
                 });
 
                 Ok(Self {
@@ -95,14 +119,21 @@ impl PtyProcessInner {
                 }
 
                 env::set_var("TERM", "xterm-256color");
+                // 让程序按 UTF-8 输出，浏览器端按 UTF-8 解码不会乱码
+                if !locale.is_empty() {
+                    env::set_var("LANG", &locale);
+                    env::set_var("LC_ALL", &locale);
+                }
 
                 let args: Vec<CString> = command
                     .iter()
                     .map(|s| CString::new(s.as_str()).unwrap())
                     .collect();
 
-                let _ = execvp(&args[0], &args);
-                std::process::exit(1);
+                // execvp 只会在失败时返回；子进程的 stderr 就是 PTY，错误信息会直接显示在浏览器里
+                let Err(e) = execvp(&args[0], &args);
+                eprint!("[failed to execute {}: {}]\r\n", command[0], e);
+                std::process::exit(127);
             }
         }
     }
@@ -4,7 +4,7 @@ use bytes::Bytes;
 use std::ffi::CString;
 use std::os::windows::io::FromRawHandle;
 use std::ptr;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use windows::Win32::Foundation::*;
 use windows::Win32::Security::*;
 use windows::Win32::Storage::FileSystem::*;
@@ -25,8 +25,10 @@ impl PtyProcessInner {
         command: Vec<String>,
         size: PtySize,
         cwd: Option<String>,
-        output_tx: mpsc::UnboundedSender<Bytes>,
+        _locale: String,
+        output_tx: mpsc::Sender<Bytes>,
         mut input_rx: mpsc::UnboundedReceiver<Bytes>,
+        exit_tx: watch::Sender<Option<i32>>,
     ) -> Result<Self> {
         unsafe {
             // 获取唯一的管道计数器
@@ -211,7 +213,7 @@ impl PtyProcessInner {
                                 match out_file.read(&mut buffer) {
                                     Ok(0) => break,
                                     Ok(n) => {
-                                        if output_tx.send(Bytes::copy_from_slice(&buffer[..n])).is_err() {
+                                        if output_tx.blocking_send(Bytes::copy_from_slice(&buffer[..n])).is_err() {
                                             break;
                                         }
                                     }
@@ -237,9 +239,16 @@ impl PtyProcessInner {
                 }
             });
 
-            tokio::spawn(async move {
+            tokio::task::spawn_blocking(move || {
                 WaitForSingleObject(process_handle, INFINITE);
+                let mut exit_code: u32 = 0;
+                let code = if GetExitCodeProcess(process_handle, &mut exit_code).is_ok() {
+                    exit_code as i32
+                } else {
+                    -1
+                };
                 CloseHandle(process_handle).ok();
+                let _ = exit_tx.send(Some(code));
             });
 
             Ok(Self {
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// 以可读文本追加记录一个会话的输入输出，不可打印字节转义为 \xNN
+pub struct AuditLog {
+    file: File,
+    session: u64,
+    input_only: bool,
+}
+
+impl AuditLog {
+    pub fn open(path: &str, input_only: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path))?;
+        Ok(Self {
+            file,
+            session: NEXT_SESSION.fetch_add(1, Ordering::Relaxed),
+            input_only,
+        })
+    }
+
+    pub fn session_start(&mut self, command: &[String]) {
+        self.write_line("START", command.join(" ").as_bytes());
+    }
+
+    pub fn input(&mut self, data: &[u8]) {
+        self.write_line("IN", data);
+    }
+
+    pub fn output(&mut self, data: &[u8]) {
+        if !self.input_only {
+            self.write_line("OUT", data);
+        }
+    }
+
+    fn write_line(&mut self, direction: &str, data: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let line = format!(
+            "[{}.{:03}] session={} {} {}\n",
+            timestamp.as_secs(),
+            timestamp.subsec_millis(),
+            self.session,
+            direction,
+            data.escape_ascii()
+        );
+        // 审计日志写失败不应中断会话
+        let _ = self.file.write_all(line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ttyd-rust-audit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn input_is_logged_with_control_bytes_escaped() {
+        let path = temp_log("input");
+        let mut log = AuditLog::open(&path, false).unwrap();
+        log.input(b"ls\r");
+        log.output(b"file\r\n");
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" IN ls\\r"), "{}", lines[0]);
+        assert!(lines[1].ends_with(" OUT file\\r\\n"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn input_only_drops_output() {
+        let path = temp_log("input-only");
+        let mut log = AuditLog::open(&path, true).unwrap();
+        log.input(b"whoami\r");
+        log.output(b"root\r\n");
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(text.contains(" IN whoami\\r"));
+        assert!(!text.contains(" OUT "));
+    }
+}
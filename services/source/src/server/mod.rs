@@ -1,4 +1,44 @@
+use crate::http;
+use crate::pty::{PtyProcess, PtySize};
+use axum::{routing::get, Router};
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::{broadcast, oneshot};
+use tower_http::trace::TraceLayer;
+use tracing::info;
+
+/// 写入 PTY 前如何转换回车换行；Auto 原样透传，交给 PTY 的行规程处理
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+pub enum NewlineMode {
+    #[default]
+    Auto,
+    Cr,
+    Lf,
+    Crlf,
+}
+
+impl NewlineMode {
+    pub fn translate(self, input: &str) -> String {
+        if self == NewlineMode::Auto {
+            return input.to_string();
+        }
+        // 先统一成 \r，再换成目标换行
+        let normalized = input.replace("\r\n", "\r").replace('\n', "\r");
+        match self {
+            NewlineMode::Lf => normalized.replace('\r', "\n"),
+            NewlineMode::Crlf => normalized.replace('\r', "\r\n"),
+            _ => normalized,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -6,11 +46,26 @@ pub struct Config {
     pub interface: String,
     pub command: Vec<String>,
     pub cwd: Option<String>,
+    /// 已认证客户端可以在 Init 里指定工作目录，但必须位于该目录之下
+    pub cwd_root: Option<String>,
     pub credential: Option<String>,
     pub writable: bool,
     pub check_origin: bool,
     pub max_clients: usize,
     pub once: bool,
+    /// 合并 PTY 输出的时间窗口（毫秒），0 表示每次读到就立即发送
+    pub coalesce_ms: u64,
+    /// 审计日志路径，按会话追加记录输入输出
+    pub log_file: Option<String>,
+    /// 审计日志只记录键盘输入
+    pub log_input_only: bool,
+    pub newline: NewlineMode,
+    /// 单条客户端 WebSocket 消息的最大字节数，超过时直接断开连接
+    pub max_message_size: usize,
+    /// 子进程的 LANG/LC_ALL（仅 Unix），为空时沿用服务端自己的环境
+    pub locale: String,
+    /// 进程退出而连接仍在时重新启动命令，而不是关闭连接
+    pub restart: bool,
 }
 
 impl Default for Config {
@@ -18,25 +73,519 @@ impl Default for Config {
         Self {
             port: 7681,
             interface: "0.0.0.0".to_string(),
-            command: vec!["bash".to_string()],
+            command: default_command(),
             cwd: None,
+            cwd_root: None,
             credential: None,
-            writable: true,
+            writable: false,
             check_origin: false,
             max_clients: 0,
             once: false,
+            coalesce_ms: 5,
+            log_file: None,
+            log_input_only: false,
+            newline: NewlineMode::Auto,
+            max_message_size: 1024 * 1024,
+            locale: "C.UTF-8".to_string(),
+            restart: false,
         }
     }
 }
 
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+/// 没有指定命令时使用用户自己的 shell：Unix 取 $SHELL，依次退回 bash、sh；
+/// Windows 取 %COMSPEC%，没有时用 PowerShell
+pub fn default_command() -> Vec<String> {
+    #[cfg(unix)]
+    {
+        let shell = std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .or_else(|| {
+                ["bash", "sh"]
+                    .into_iter()
+                    .find(|shell| crate::pty::command_exists(shell, None))
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| "sh".to_string());
+        vec![shell]
+    }
+    #[cfg(windows)]
+    {
+        let shell = std::env::var("COMSPEC")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| "powershell.exe".to_string());
+        vec![shell]
+    }
+}
+
+/// 以链式调用构造 `Config`，`build()` 时校验参数
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.config.interface = interface.into();
+        self
+    }
+
+    pub fn command(mut self, command: Vec<String>) -> Self {
+        self.config.command = command;
+        self
+    }
+
+    pub fn cwd(mut self, cwd: Option<String>) -> Self {
+        self.config.cwd = cwd;
+        self
+    }
+
+    pub fn cwd_root(mut self, cwd_root: Option<String>) -> Self {
+        self.config.cwd_root = cwd_root;
+        self
+    }
+
+    pub fn credential(mut self, credential: Option<String>) -> Self {
+        self.config.credential = credential;
+        self
+    }
+
+    pub fn writable(mut self, writable: bool) -> Self {
+        self.config.writable = writable;
+        self
+    }
+
+    pub fn check_origin(mut self, check_origin: bool) -> Self {
+        self.config.check_origin = check_origin;
+        self
+    }
+
+    pub fn max_clients(mut self, max_clients: usize) -> Self {
+        self.config.max_clients = max_clients;
+        self
+    }
+
+    pub fn once(mut self, once: bool) -> Self {
+        self.config.once = once;
+        self
+    }
+
+    pub fn coalesce_ms(mut self, coalesce_ms: u64) -> Self {
+        self.config.coalesce_ms = coalesce_ms;
+        self
+    }
+
+    pub fn log_file(mut self, log_file: Option<String>) -> Self {
+        self.config.log_file = log_file;
+        self
+    }
+
+    pub fn log_input_only(mut self, log_input_only: bool) -> Self {
+        self.config.log_input_only = log_input_only;
+        self
+    }
+
+    pub fn newline(mut self, newline: NewlineMode) -> Self {
+        self.config.newline = newline;
+        self
+    }
+
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.config.max_message_size = max_message_size;
+        self
+    }
+
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.config.locale = locale.into();
+        self
+    }
+
+    pub fn restart(mut self, restart: bool) -> Self {
+        self.config.restart = restart;
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Config> {
+        if self.config.command.is_empty() || self.config.command[0].is_empty() {
+            anyhow::bail!("Command must not be empty");
+        }
+        if self.config.interface.is_empty() {
+            anyhow::bail!("Interface must not be empty");
+        }
+        if self.config.max_message_size == 0 {
+            anyhow::bail!("Max message size must be greater than zero");
+        }
+        if self.config.locale.contains(['\0', '=']) {
+            anyhow::bail!("Invalid locale: {:?}", self.config.locale);
+        }
+        Ok(self.config)
+    }
+}
+
+/// 把客户端请求的目录解析到 root 之下，解析符号链接和 `..` 后仍需在 root 内且是目录
+pub fn resolve_client_cwd(root: &str, requested: &str) -> anyhow::Result<String> {
+    let root = Path::new(root)
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Invalid cwd root {}: {}", root, e))?;
+    let candidate = root
+        .join(requested.trim_start_matches('/'))
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Invalid cwd {}: {}", requested, e))?;
+
+    if !candidate.starts_with(&root) {
+        anyhow::bail!("cwd {} is outside the allowed root", requested);
+    }
+    if !candidate.is_dir() {
+        anyhow::bail!("cwd {} is not a directory", requested);
+    }
+    Ok(candidate.to_string_lossy().into_owned())
+}
+
+/// 命令模板里允许出现的占位符，其余 `%...%` 原样保留
+pub const COMMAND_PLACEHOLDERS: &[&str] = &["USER", "SESSION"];
+
+/// 占位符的值只允许字母、数字和 `._-`，且不能以 `-` 开头，避免被当作参数或注入
+fn is_safe_placeholder_value(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('-')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// 把命令模板中的 `%USER%`、`%SESSION%` 替换为本次连接的值；
+/// 用到了某个占位符却没有值或值不合法时返回错误
+pub fn expand_command(template: &[String], values: &HashMap<&str, String>) -> anyhow::Result<Vec<String>> {
+    template
+        .iter()
+        .map(|arg| {
+            let mut expanded = arg.clone();
+            for name in COMMAND_PLACEHOLDERS {
+                let placeholder = format!("%{}%", name);
+                if !expanded.contains(&placeholder) {
+                    continue;
+                }
+                match values.get(name) {
+                    Some(value) if is_safe_placeholder_value(value) => {
+                        expanded = expanded.replace(&placeholder, value);
+                    }
+                    Some(value) => anyhow::bail!("Invalid value {:?} for {}", value, placeholder),
+                    None => anyhow::bail!("No value for {}", placeholder),
+                }
+            }
+            Ok(expanded)
+        })
+        .collect()
+}
+
+/// 每个共享会话保留的最近输出字节数，观看者加入时先回放这些内容恢复屏幕
+const SESSION_HISTORY_BYTES: usize = 256 * 1024;
+
+/// 驱动者断开后暂存的 PTY、它的输出通道，以及 --restart 需要的启动参数
+pub(crate) struct DetachedPty {
+    pub process: PtyProcess,
+    pub output_rx: Receiver<Bytes>,
+    pub respawn: Option<(Vec<String>, PtySize, Option<String>)>,
+}
+
+/// 共享会话：PTY 输出的广播通道，以及最近输出的环形缓冲
+#[derive(Clone)]
+pub struct Session {
+    tx: broadcast::Sender<Bytes>,
+    history: Arc<Mutex<VecDeque<u8>>>,
+    // 驱动者断开期间，向暂存 PTY 的后台任务申请取回它
+    takeover: Arc<Mutex<Option<oneshot::Sender<oneshot::Sender<DetachedPty>>>>>,
+}
+
+impl Session {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            tx,
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            takeover: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 记录并广播一段输出。和 `attach` 用同一把锁，加入的观看者拿到的历史和之后的广播不重不漏
+    pub fn publish(&self, data: Bytes) {
+        let mut history = self.history.lock().unwrap();
+        history.extend(data.iter());
+        let excess = history.len().saturating_sub(SESSION_HISTORY_BYTES);
+        history.drain(..excess);
+        let _ = self.tx.send(data);
+    }
+
+    /// 返回目前为止的输出历史，并订阅之后的输出
+    pub fn attach(&self) -> (Vec<u8>, broadcast::Receiver<Bytes>) {
+        let history = self.history.lock().unwrap();
+        (history.iter().copied().collect(), self.tx.subscribe())
+    }
+
+    /// 驱动者断开后保留 PTY：宽限期内后台任务继续读取输出、记录历史并广播给观看者，
+    /// 期间重新连上的驱动者可以用 `reattach` 取回；超时或进程退出后结束进程并移除会话
+    pub(crate) fn detach(&self, state: Arc<AppState>, id: String, mut pty: DetachedPty, grace: Duration) {
+        let (takeover_tx, mut takeover_rx) = oneshot::channel::<oneshot::Sender<DetachedPty>>();
+        *self.takeover.lock().unwrap() = Some(takeover_tx);
+        let session = self.clone();
+        tokio::spawn(async move {
+            let deadline = tokio::time::sleep(grace);
+            tokio::pin!(deadline);
+            let mut exited = false;
+            loop {
+                let reply = tokio::select! {
+                    output = pty.output_rx.recv() => match output {
+                        Some(data) => {
+                            session.publish(data);
+                            continue;
+                        }
+                        None => {
+                            let code = pty.process.wait().await.unwrap_or(-1);
+                            info!("Detached PTY process of session {} exited with code {}", id, code);
+                            exited = true;
+                            None
+                        }
+                    },
+                    reply = &mut takeover_rx => reply.ok(),
+                    _ = &mut deadline => {
+                        info!("Session {} was not reattached within {:?}", id, grace);
+                        None
+                    }
+                };
+                if let Some(reply) = reply {
+                    // 对方已经放弃等待时收回 PTY，和超时一样处理
+                    match reply.send(pty) {
+                        Ok(()) => return,
+                        Err(returned) => pty = returned,
+                    }
+                }
+                break;
+            }
+
+            session.takeover.lock().unwrap().take();
+            state.sessions.lock().unwrap().remove(&id);
+            if !exited {
+                info!("Killing PTY process {}", pty.process.pid);
+                let _ = pty.process.kill().await;
+            }
+        });
+    }
+
+    /// 会话的驱动者已断开且仍在宽限期内时取回暂存的 PTY，否则返回 None
+    pub(crate) async fn reattach(&self) -> Option<DetachedPty> {
+        let takeover = self.takeover.lock().unwrap().take()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        takeover.send(reply_tx).ok()?;
+        reply_rx.await.ok()
+    }
+}
+
 pub struct AppState {
     pub config: Config,
+    /// 共享会话：会话 id 到会话的输出广播和历史，观看者订阅同一份输出
+    pub sessions: Mutex<HashMap<String, Session>>,
 }
 
 impl AppState {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        }
     }
 }
 
+/// 全部 HTTP 和 WebSocket 路由，嵌入方可以把它合并进自己的 axum 应用
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(http::index_handler))
+        .route("/token", get(http::token_handler))
+        .route("/ws", get(websocket::ws_handler))
+        .route("/js/*path", get(http::static_file_handler_path))
+        .route("/css/*path", get(http::static_file_handler_path))
+        .route("/xterm.min.css", get(http::static_file_handler_root))
+        .with_state(state)
+        .layer(TraceLayer::new_for_http())
+}
+
+/// 绑定配置里的地址并运行服务器，`shutdown` 完成后停止接受新连接并退出
+pub async fn serve(config: Config, shutdown: impl Future<Output = ()> + Send + 'static) -> anyhow::Result<()> {
+    let addr: SocketAddr = format!("{}:{}", config.interface, config.port).parse()?;
+    let listener = TcpListener::bind(addr).await?;
+    serve_listener(listener, config, shutdown).await
+}
+
+/// 在已绑定的监听器上运行服务器，适合端口为 0 或需要先拿到实际地址的场景
+pub async fn serve_listener(
+    listener: TcpListener,
+    config: Config,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    info!("Server listening on http://{}", listener.local_addr()?);
+    let state = Arc::new(AppState::new(config));
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+pub mod audit;
+pub mod title;
 pub mod websocket;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_accepts_a_valid_config() {
+        let config = Config::builder()
+            .port(8080)
+            .interface("127.0.0.1")
+            .command(vec!["bash".to_string()])
+            .writable(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.interface, "127.0.0.1");
+        assert_eq!(config.command, vec!["bash".to_string()]);
+        assert!(config.writable);
+    }
+
+    #[test]
+    fn builder_rejects_invalid_values() {
+        assert!(Config::builder().command(Vec::new()).build().is_err());
+        assert!(Config::builder().command(vec![String::new()]).build().is_err());
+        assert!(Config::builder().interface("").build().is_err());
+    }
+
+    /// 建一个临时的 cwd 根目录，里面有子目录 `projects/app` 和文件 `notes.txt`
+    fn cwd_root(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("ttyd-rust-cwd-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("projects/app")).unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn client_cwd_resolves_inside_the_root() {
+        let root = cwd_root("inside");
+        let root_str = root.to_str().unwrap();
+        let expected = root.join("projects/app").to_string_lossy().into_owned();
+        assert_eq!(resolve_client_cwd(root_str, "projects/app").unwrap(), expected);
+        // 开头的 / 也当作相对于 root
+        assert_eq!(resolve_client_cwd(root_str, "/projects/app").unwrap(), expected);
+        assert_eq!(resolve_client_cwd(root_str, "projects/app/../app").unwrap(), expected);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn client_cwd_cannot_escape_the_root() {
+        let root = cwd_root("escape");
+        let root_str = root.to_str().unwrap();
+        assert!(resolve_client_cwd(root_str, "..").is_err());
+        assert!(resolve_client_cwd(root_str, "projects/../../").is_err());
+        assert!(resolve_client_cwd(root_str, "notes.txt").is_err());
+        assert!(resolve_client_cwd(root_str, "missing").is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(std::env::temp_dir(), root.join("out")).unwrap();
+            assert!(resolve_client_cwd(root_str, "out").is_err());
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    fn template(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn expand_command_fills_in_user_and_session() {
+        let mut values = HashMap::new();
+        values.insert("USER", "alice".to_string());
+        values.insert("SESSION", "demo-1".to_string());
+        let command = expand_command(&template(&["tmux", "new", "-As", "%USER%-%SESSION%", "100%"]), &values).unwrap();
+        assert_eq!(command, template(&["tmux", "new", "-As", "alice-demo-1", "100%"]));
+    }
+
+    #[test]
+    fn expand_command_rejects_missing_or_unsafe_values() {
+        let mut values = HashMap::new();
+        assert!(expand_command(&template(&["login", "%USER%"]), &values).is_err());
+        // 没用到的占位符不需要值
+        assert!(expand_command(&template(&["bash"]), &values).is_ok());
+
+        for bad in ["", "-rf", "a b", "x;rm", "../etc"] {
+            values.insert("USER", bad.to_string());
+            assert!(expand_command(&template(&["login", "%USER%"]), &values).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn newline_modes_translate_a_bare_carriage_return() {
+        assert_eq!(NewlineMode::Auto.translate("ls\r"), "ls\r");
+        assert_eq!(NewlineMode::Cr.translate("ls\r"), "ls\r");
+        assert_eq!(NewlineMode::Lf.translate("ls\r"), "ls\n");
+        assert_eq!(NewlineMode::Crlf.translate("ls\r"), "ls\r\n");
+        // 已经是 CRLF 的输入不会被翻倍
+        assert_eq!(NewlineMode::Crlf.translate("a\r\nb\n"), "a\r\nb\r\n");
+        assert_eq!(NewlineMode::Cr.translate("a\r\nb\n"), "a\rb\r");
+    }
+
+    // 只有这个测试会修改 SHELL，其余测试不依赖默认命令的具体值
+    #[cfg(unix)]
+    #[test]
+    fn default_command_follows_the_shell_variable() {
+        let saved = std::env::var_os("SHELL");
+
+        std::env::set_var("SHELL", "/bin/sh");
+        assert_eq!(default_command(), vec!["/bin/sh".to_string()]);
+        assert_eq!(Config::default().command, vec!["/bin/sh".to_string()]);
+
+        // SHELL 为空时退回 bash 或 sh
+        std::env::set_var("SHELL", "");
+        let fallback = default_command();
+        assert!(fallback == ["bash"] || fallback == ["sh"], "{:?}", fallback);
+
+        match saved {
+            Some(shell) => std::env::set_var("SHELL", shell),
+            None => std::env::remove_var("SHELL"),
+        }
+    }
+
+    #[test]
+    fn session_history_keeps_only_the_most_recent_output() {
+        let session = Session::new(16);
+        session.publish(Bytes::from(vec![b'a'; SESSION_HISTORY_BYTES]));
+        session.publish(Bytes::from_static(b"tail"));
+
+        let (history, mut rx) = session.attach();
+        assert_eq!(history.len(), SESSION_HISTORY_BYTES);
+        assert!(history.ends_with(b"atail"));
+
+        // 加入之后的输出只走广播，不会再出现在这次拿到的历史里
+        session.publish(Bytes::from_static(b"live"));
+        assert_eq!(rx.try_recv().unwrap(), Bytes::from_static(b"live"));
+        assert!(session.attach().0.ends_with(b"taillive"));
+    }
+
+    #[test]
+    fn locale_defaults_to_utf8_and_rejects_env_syntax() {
+        let config = Config::builder().command(vec!["sh".to_string()]).build().unwrap();
+        assert_eq!(config.locale, "C.UTF-8");
+        let config = Config::builder().command(vec!["sh".to_string()]).locale("en_US.UTF-8").build().unwrap();
+        assert_eq!(config.locale, "en_US.UTF-8");
+        assert!(Config::builder().command(vec!["sh".to_string()]).locale("LANG=C").build().is_err());
+    }
+}
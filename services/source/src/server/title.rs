@@ -0,0 +1,93 @@
+/// 标题序列最长保留的字节数，超过后丢弃，避免异常输出占用内存
+const MAX_PENDING: usize = 4096;
+
+/// 从 PTY 输出中识别 OSC 0/2 标题序列（`ESC ] 0 ; 标题 BEL` 或以 `ESC \` 结尾），
+/// 序列可以被拆在多次输出里
+#[derive(Default)]
+pub struct TitleTracker {
+    pending: Vec<u8>,
+    last: Option<String>,
+}
+
+impl TitleTracker {
+    /// 处理一段输出，标题发生变化时返回新标题
+    pub fn feed(&mut self, data: &[u8]) -> Option<String> {
+        self.pending.extend_from_slice(data);
+        let mut changed = None;
+
+        loop {
+            let Some(start) = find(&self.pending, b"\x1b]") else {
+                // 末尾单独的 ESC 可能是下一段序列的开头
+                let keep = if self.pending.last() == Some(&0x1b) { 1 } else { 0 };
+                self.pending.drain(..self.pending.len() - keep);
+                break;
+            };
+            let body = &self.pending[start + 2..];
+            let end = body
+                .iter()
+                .enumerate()
+                .find(|&(i, &b)| b == 0x07 || (b == 0x1b && body.get(i + 1) == Some(&b'\\')))
+                .map(|(i, &b)| (i, if b == 0x07 { 1 } else { 2 }));
+
+            let Some((len, terminator)) = end else {
+                // 序列还没结束，保留到下一段输出
+                self.pending.drain(..start);
+                if self.pending.len() > MAX_PENDING {
+                    self.pending.clear();
+                }
+                break;
+            };
+
+            let sequence = &body[..len];
+            if let Some(title) = sequence
+                .strip_prefix(b"0;")
+                .or_else(|| sequence.strip_prefix(b"2;"))
+            {
+                let title = String::from_utf8_lossy(title).into_owned();
+                if self.last.as_ref() != Some(&title) {
+                    self.last = Some(title.clone());
+                    changed = Some(title);
+                }
+            }
+            self.pending.drain(..start + 2 + len + terminator);
+        }
+
+        changed
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_bel_and_st_terminated_titles() {
+        let mut tracker = TitleTracker::default();
+        assert_eq!(tracker.feed(b"plain output"), None);
+        assert_eq!(tracker.feed(b"a\x1b]0;vim main.rs\x07b"), Some("vim main.rs".to_string()));
+        assert_eq!(tracker.feed(b"\x1b]2;htop\x1b\\"), Some("htop".to_string()));
+        // 其他 OSC 序列（例如设置颜色）不是标题
+        assert_eq!(tracker.feed(b"\x1b]4;1;rgb:ff/00/00\x07"), None);
+    }
+
+    #[test]
+    fn titles_split_across_reads_are_joined() {
+        let mut tracker = TitleTracker::default();
+        assert_eq!(tracker.feed(b"prompt$ \x1b"), None);
+        assert_eq!(tracker.feed(b"]0;long "), None);
+        assert_eq!(tracker.feed(b"title\x07$ "), Some("long title".to_string()));
+    }
+
+    #[test]
+    fn repeated_titles_are_reported_once() {
+        let mut tracker = TitleTracker::default();
+        assert_eq!(tracker.feed(b"\x1b]0;bash\x07"), Some("bash".to_string()));
+        assert_eq!(tracker.feed(b"\x1b]0;bash\x07"), None);
+        // 同一段里有多个标题时返回最后变化的那个
+        assert_eq!(tracker.feed(b"\x1b]0;make\x07\x1b]0;bash\x07"), Some("bash".to_string()));
+    }
+}
@@ -1,27 +1,95 @@
 use crate::protocol::{ClientMessage, ServerMessage};
 use crate::pty::{PtyProcess, PtySize};
-use crate::server::AppState;
+use crate::server::audit::AuditLog;
+use crate::server::title::TitleTracker;
+use crate::server::{expand_command, resolve_client_cwd, AppState, Config, DetachedPty, Session};
 use axum::extract::ws::{Message, WebSocket};
-use axum::extract::{State, WebSocketUpgrade};
+use axum::extract::{Query, State, WebSocketUpgrade};
 use axum::response::Response;
 use bytes::Bytes;
 use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, Receiver};
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let session_id = params.get("session").cloned();
+    // 超长的帧在读取阶段就会报错并断开，不会整条缓冲后再交给解析
+    let max_size = state.config.max_message_size;
+    ws.max_message_size(max_size)
+        .max_frame_size(max_size)
+        .on_upgrade(|socket| handle_socket(socket, state, session_id))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+// 共享会话广播通道的容量，观看者落后太多时会跳过旧输出
+const SESSION_BROADCAST_CAPACITY: usize = 256;
+
+// 开启 --restart 时，进程退出到重新启动之间的等待时间
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// 共享会话的驱动者断开后，保留 PTY 等它重新连上的时间
+const SESSION_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// 配置了审计日志时打开它并写入本次连接的开头；打开失败只记录错误，不影响连接
+fn open_audit_log(config: &Config) -> Option<AuditLog> {
+    let path = config.log_file.as_ref()?;
+    match AuditLog::open(path, config.log_input_only) {
+        Ok(mut log) => {
+            log.session_start(&config.command);
+            Some(log)
+        }
+        Err(e) => {
+            error!("{:#}", e);
+            None
+        }
+    }
+}
+
+/// 把广播通道转成 mpsc 通道，让观看者和驱动者走同一套输出逻辑
+fn bridge_broadcast(mut rx: broadcast::Receiver<Bytes>) -> Receiver<Bytes> {
+    let (tx, out_rx) = mpsc::channel(SESSION_BROADCAST_CAPACITY);
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(data) => {
+                    if tx.send(data).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Session viewer lagged, skipped {} messages", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    out_rx
+}
+
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, session_id: Option<String>) {
     let (mut sender, mut receiver) = socket.split();
     let mut pty_process: Option<PtyProcess> = None;
-    let mut output_rx: Option<tokio::sync::mpsc::UnboundedReceiver<Bytes>> = None;
+    // 加入已有共享会话的客户端只能观看，输入会被丢弃
+    let mut viewer = false;
+    // 驱动者把 PTY 输出同时发到该广播通道，供观看者订阅
+    let mut session: Option<Session> = None;
+    // 有界通道：发送给客户端阻塞时不再读取 PTY 输出，形成服务端流控
+    let mut output_rx: Option<Receiver<Bytes>> = None;
+    let coalesce_window = Duration::from_millis(state.config.coalesce_ms);
+    let mut audit_log: Option<AuditLog> = None;
+    let mut title_tracker = TitleTracker::default();
     let mut paused = false;
     let mut initialized = false;
+    // 开启 --restart 时记住启动参数（命令、当前窗口大小、工作目录），进程退出后按原样重启
+    let mut respawn: Option<(Vec<String>, PtySize, Option<String>)> = None;
 
     info!("WebSocket connection established");
 
@@ -48,17 +116,65 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
     loop {
         tokio::select! {
-            Some(data) = async {
-                if paused || !initialized {
-                    None
-                } else {
-                    output_rx.as_mut()?.recv().await
-                }
-            } => {
-                let msg = ServerMessage::Output(data.to_vec());
-                if sender.send(Message::Binary(msg.to_bytes())).await.is_err() {
-                    error!("Failed to send PTY output to client");
-                    break;
+            output = async { output_rx.as_mut()?.recv().await }, if initialized && !paused => {
+                match output {
+                    Some(data) => {
+                        let data = match output_rx.as_mut() {
+                            Some(rx) => coalesce_output(rx, data, coalesce_window).await,
+                            None => data.to_vec(),
+                        };
+                        if let Some(ref mut log) = audit_log {
+                            log.output(&data);
+                        }
+                        if let Some(ref session) = session {
+                            session.publish(Bytes::from(data.clone()));
+                        }
+                        if let Some(title) = title_tracker.feed(&data) {
+                            let msg = ServerMessage::SetWindowTitle(title);
+                            if sender.send(Message::Binary(msg.to_bytes())).await.is_err() {
+                                error!("Failed to send window title to client");
+                                break;
+                            }
+                        }
+                        let msg = ServerMessage::Output(data);
+                        if sender.send(Message::Binary(msg.to_bytes())).await.is_err() {
+                            error!("Failed to send PTY output to client");
+                            break;
+                        }
+                    }
+                    None => {
+                        // 输出通道关闭说明 PTY 进程已经退出
+                        let code = match pty_process.take() {
+                            Some(process) => process.wait().await.unwrap_or(-1),
+                            None => -1,
+                        };
+                        info!("PTY process exited with code {}", code);
+                        if let Some((command, size, cwd)) = respawn.clone() {
+                            // 避免立即退出的命令把重启变成忙循环
+                            tokio::time::sleep(RESTART_DELAY).await;
+                            match PtyProcess::spawn(command, size, cwd, state.config.locale.clone()).await {
+                                Ok((process, rx)) => {
+                                    info!("PTY process restarted with PID: {}", process.pid);
+                                    pty_process = Some(process);
+                                    output_rx = Some(rx);
+                                    // 清屏后告诉用户进程重启过
+                                    let notice = format!("\x1b[2J\x1b[3J\x1b[H[process exited with code {}, restarted]\r\n", code);
+                                    if let Some(ref session) = session {
+                                        session.publish(Bytes::from(notice.clone()));
+                                    }
+                                    let msg = ServerMessage::Output(notice.into_bytes());
+                                    if sender.send(Message::Binary(msg.to_bytes())).await.is_err() {
+                                        break;
+                                    }
+                                    continue;
+                                }
+                                Err(e) => error!("Failed to restart PTY process: {}", e),
+                            }
+                        }
+                        let msg = ServerMessage::ProcessExit { code };
+                        let _ = sender.send(Message::Binary(msg.to_bytes())).await;
+                        break;
+                    }
                 }
             }
 
@@ -86,29 +202,130 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                     rows: if init.rows > 0 { init.rows } else { 24 },
                                 };
 
+                                let existing = session_id
+                                    .as_ref()
+                                    .and_then(|id| state.sessions.lock().unwrap().get(id).cloned());
+                                if let Some(existing) = existing {
+                                    let reattached = existing.reattach().await;
+                                    let history = match reattached {
+                                        // 驱动者断开后在宽限期内重新连上，接管原来的 PTY
+                                        Some(detached) => {
+                                            info!("Driver reattached to session {:?}", session_id);
+                                            let (history, _) = existing.attach();
+                                            if let Err(e) = detached.process.resize(size.clone()).await {
+                                                error!("Failed to resize PTY: {}", e);
+                                            }
+                                            respawn = detached.respawn.map(|(command, _, cwd)| (command, size, cwd));
+                                            pty_process = Some(detached.process);
+                                            output_rx = Some(detached.output_rx);
+                                            session = Some(existing);
+                                            audit_log = open_audit_log(&state.config);
+                                            history
+                                        }
+                                        None => {
+                                            info!("Attaching read-only viewer to session {:?}", session_id);
+                                            let (history, rx) = existing.attach();
+                                            output_rx = Some(bridge_broadcast(rx));
+                                            viewer = true;
+                                            history
+                                        }
+                                    };
+                                    initialized = true;
+                                    let _ = sender.send(Message::Binary(ServerMessage::Ready.to_bytes())).await;
+                                    // 先回放最近的输出，恢复出当前屏幕
+                                    if !history.is_empty() {
+                                        let msg = ServerMessage::Output(history);
+                                        if sender.send(Message::Binary(msg.to_bytes())).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    continue;
+                                }
+
+                                // 只有开启认证并配置了 cwd_root 时才接受客户端指定的目录
+                                let cwd = match (&init.cwd, &state.config.cwd_root, &state.config.credential) {
+                                    (Some(requested), Some(root), Some(_)) => match resolve_client_cwd(root, requested) {
+                                        Ok(dir) => Some(dir),
+                                        Err(e) => {
+                                            warn!("Rejected client cwd: {}", e);
+                                            let msg = ServerMessage::Output(format!("[{}]\r\n", e).into_bytes());
+                                            let _ = sender.send(Message::Binary(msg.to_bytes())).await;
+                                            break;
+                                        }
+                                    },
+                                    _ => state.config.cwd.clone(),
+                                };
+
+                                // 用户名优先取自 "user:password" 形式的凭据，否则在已认证时采用 Init 中的 user
+                                let mut values = HashMap::new();
+                                if let Some(ref credential) = state.config.credential {
+                                    match credential.split_once(':') {
+                                        Some((user, _)) => {
+                                            values.insert("USER", user.to_string());
+                                        }
+                                        None => {
+                                            if let Some(user) = init.user.clone() {
+                                                values.insert("USER", user);
+                                            }
+                                        }
+                                    }
+                                }
+                                if let Some(ref id) = session_id {
+                                    values.insert("SESSION", id.clone());
+                                }
+                                let command = match expand_command(&state.config.command, &values) {
+                                    Ok(command) => command,
+                                    Err(e) => {
+                                        warn!("Failed to expand command template: {}", e);
+                                        let msg = ServerMessage::Output(format!("[{}]\r\n", e).into_bytes());
+                                        let _ = sender.send(Message::Binary(msg.to_bytes())).await;
+                                        break;
+                                    }
+                                };
+
                                 info!("Spawning PTY with size {}x{}", size.cols, size.rows);
+                                if state.config.restart {
+                                    respawn = Some((command.clone(), size.clone(), cwd.clone()));
+                                }
                                 match PtyProcess::spawn(
-                                    state.config.command.clone(),
+                                    command,
                                     size,
-                                    state.config.cwd.clone(),
+                                    cwd,
+                                    state.config.locale.clone(),
                                 ).await {
                                     Ok((process, rx)) => {
                                         info!("PTY process spawned with PID: {}", process.pid);
                                         pty_process = Some(process);
                                         output_rx = Some(rx);
+                                        if let Some(ref id) = session_id {
+                                            let new_session = Session::new(SESSION_BROADCAST_CAPACITY);
+                                            state.sessions.lock().unwrap().insert(id.clone(), new_session.clone());
+                                            session = Some(new_session);
+                                        }
                                         initialized = true;
+                                        audit_log = open_audit_log(&state.config);
                                         debug!("PTY initialized, ready to receive output");
+                                        if sender.send(Message::Binary(ServerMessage::Ready.to_bytes())).await.is_err() {
+                                            error!("Failed to send ready signal to client");
+                                            break;
+                                        }
                                     }
                                     Err(e) => {
                                         error!("Failed to spawn PTY process: {}", e);
+                                        let msg = ServerMessage::Output(format!("[{}]\r\n", e).into_bytes());
+                                        let _ = sender.send(Message::Binary(msg.to_bytes())).await;
                                         break;
                                     }
                                 }
                             }
                             Ok(ClientMessage::Input(data)) => {
-                                if !state.config.writable {
+                                if !state.config.writable || viewer {
                                     continue;
                                 }
+                                let data = state.config.newline.translate(&data);
+                                if let Some(ref mut log) = audit_log {
+                                    log.input(data.as_bytes());
+                                }
                                 if let Some(ref process) = pty_process {
                                     if let Err(e) = process.write(Bytes::from(data)).await {
                                         error!("Failed to write to PTY: {}", e);
@@ -118,8 +335,12 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 }
                             }
                             Ok(ClientMessage::Resize { cols, rows }) => {
+                                // 观看者没有 pty_process，窗口大小只由驱动者决定
                                 if let Some(ref process) = pty_process {
                                     let size = PtySize { cols, rows };
+                                    if let Some((_, ref mut last_size, _)) = respawn {
+                                        *last_size = size.clone();
+                                    }
                                     if let Err(e) = process.resize(size).await {
                                         error!("Failed to resize PTY: {}", e);
                                     }
@@ -151,6 +372,21 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         }
     }
 
+    // 驱动者断开时 PTY 还在就暂存起来等它重新连上，观看者照常收到输出；
+    // 进程已经退出时直接移除会话，广播发送端全部释放，观看者随之收到结束
+    if let (Some(session), Some(id)) = (session.take(), session_id) {
+        match (pty_process.take(), output_rx.take()) {
+            (Some(process), Some(output_rx)) => {
+                info!("Driver of session {} disconnected, keeping PTY {} for {:?}", id, process.pid, SESSION_GRACE_PERIOD);
+                let detached = DetachedPty { process, output_rx, respawn };
+                session.detach(state.clone(), id, detached, SESSION_GRACE_PERIOD);
+            }
+            _ => {
+                state.sessions.lock().unwrap().remove(&id);
+            }
+        }
+    }
+
     if let Some(process) = pty_process {
         info!("Killing PTY process {}", process.pid);
         let _ = process.kill().await;
@@ -158,3 +394,61 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
     info!("WebSocket connection closed");
 }
+
+// 单帧最多合并的字节数，避免大量输出时延迟过高
+const MAX_COALESCE_BYTES: usize = 64 * 1024;
+
+/// 在时间窗口内继续接收输出并拼接成一帧，通道关闭时直接返回已收到的部分
+async fn coalesce_output(
+    rx: &mut Receiver<Bytes>,
+    first: Bytes,
+    window: Duration,
+) -> Vec<u8> {
+    let mut buffer = first.to_vec();
+    if window.is_zero() {
+        return buffer;
+    }
+
+    let deadline = Instant::now() + window;
+    while buffer.len() < MAX_COALESCE_BYTES {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(data)) => buffer.extend_from_slice(&data),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesce_merges_writes_that_arrive_within_the_window() {
+        let (tx, mut rx) = mpsc::channel(8);
+        tx.send(Bytes::from_static(b"b")).await.unwrap();
+        tx.send(Bytes::from_static(b"c")).await.unwrap();
+        let data = coalesce_output(&mut rx, Bytes::from_static(b"a"), Duration::from_millis(50)).await;
+        assert_eq!(data, b"abc");
+    }
+
+    #[tokio::test]
+    async fn zero_window_sends_each_write_on_its_own() {
+        let (tx, mut rx) = mpsc::channel(8);
+        tx.send(Bytes::from_static(b"b")).await.unwrap();
+        let data = coalesce_output(&mut rx, Bytes::from_static(b"a"), Duration::ZERO).await;
+        assert_eq!(data, b"a");
+        assert_eq!(rx.recv().await.unwrap(), Bytes::from_static(b"b"));
+    }
+
+    #[tokio::test]
+    async fn coalesce_returns_early_when_the_pty_closes() {
+        let (tx, mut rx) = mpsc::channel(8);
+        tx.send(Bytes::from_static(b"b")).await.unwrap();
+        drop(tx);
+        let started = Instant::now();
+        let data = coalesce_output(&mut rx, Bytes::from_static(b"a"), Duration::from_secs(10)).await;
+        assert_eq!(data, b"ab");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,81 @@
+//! 不启动子进程，把库里的路由挂进自己的 axum 应用，或用 `serve_listener` 控制服务器的启停
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use ttyd_rust::{router, serve, serve_listener, AppState, Config};
+
+fn config() -> Config {
+    Config::builder().command(vec!["sh".to_string()]).build().unwrap()
+}
+
+#[tokio::test]
+async fn router_can_be_nested_in_an_embedders_app() {
+    let app = axum::Router::new().nest("/term", router(Arc::new(AppState::new(config()))));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /term/token HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "response was {:?}", response);
+    assert!(response.ends_with(r#"{"token":""}"#), "response was {:?}", response);
+
+    // /ws 同样挂在前缀下面，握手能完成
+    let (ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/term/ws", addr)).await.unwrap();
+    drop(ws);
+}
+
+#[tokio::test]
+async fn serve_listener_returns_after_shutdown() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve_listener(listener, config(), async {
+        let _ = stop_rx.await;
+    }));
+
+    let (ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    drop(ws);
+
+    stop_tx.send(()).unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), server).await.expect("server kept running");
+    result.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn serve_binds_the_configured_port() {
+    // 先借系统分配一个空闲端口，再交给 serve 自己绑定
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = Config::builder()
+        .command(vec!["sh".to_string()])
+        .interface("127.0.0.1")
+        .port(port)
+        .build()
+        .unwrap();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve(config, async {
+        let _ = stop_rx.await;
+    }));
+
+    let connect = async {
+        loop {
+            match tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}/ws", port)).await {
+                Ok((ws, _)) => return ws,
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+    };
+    let ws = tokio::time::timeout(Duration::from_secs(5), connect).await.expect("server did not start");
+    drop(ws);
+
+    stop_tx.send(()).unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), server).await.expect("server kept running");
+    result.unwrap().unwrap();
+}
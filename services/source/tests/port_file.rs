@@ -0,0 +1,42 @@
+//! 以 `--port 0` 启动服务端，从 --port-file 读出系统分配的端口并连接
+
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[test]
+fn port_zero_is_reported_through_the_port_file() {
+    let port_file = std::env::temp_dir().join(format!("ttyd-rust-port-{}", std::process::id()));
+    let _ = std::fs::remove_file(&port_file);
+    let mut server = Command::new(env!("CARGO_BIN_EXE_ttyd-rust"))
+        .args(["--port", "0", "--interface", "127.0.0.1", "--port-file"])
+        .arg(&port_file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // 文件可能还没写完，读不出数字就继续等
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let port = loop {
+        let port = std::fs::read_to_string(&port_file)
+            .ok()
+            .and_then(|text| text.trim().parse::<u16>().ok());
+        if let Some(port) = port {
+            break port;
+        }
+        if Instant::now() > deadline {
+            server.kill().unwrap();
+            panic!("port file was never written");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let connected = TcpStream::connect(("127.0.0.1", port));
+    server.kill().unwrap();
+    server.wait().unwrap();
+    let _ = std::fs::remove_file(&port_file);
+
+    assert_ne!(port, 0);
+    assert!(connected.is_ok(), "nothing listening on port {}", port);
+}
@@ -0,0 +1,55 @@
+//! 用真实的 PTY 检查 --restart：命令退出后在同一个连接里重新启动
+#![cfg(all(unix, not(feature = "mock-pty")))]
+
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use ttyd_rust::protocol::{OUTPUT, PROCESS_EXIT};
+use ttyd_rust::{serve_listener, Config};
+
+/// 从输出里取出所有 `pid=<数字>` 的数字
+fn pids(output: &str) -> Vec<u32> {
+    output
+        .split("pid=")
+        .skip(1)
+        .filter_map(|rest| {
+            let digits: String = rest.chars().take_while(|ch| ch.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn exited_command_is_respawned_with_a_new_pid() {
+    let command = ["sh", "-c", "echo pid=$$; sleep 0.2; exit 3"].map(String::from).to_vec();
+    let config = Config::builder().command(command).restart(true).build().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_listener(listener, config, std::future::pending()));
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    ws.send(Message::Binary(br#"{"columns":80,"rows":24}"#.to_vec())).await.unwrap();
+
+    let mut output = String::new();
+    let read = async {
+        while let Some(msg) = ws.next().await {
+            if let Message::Binary(data) = msg.unwrap() {
+                match data.first().map(|&b| b as char) {
+                    Some(OUTPUT) => output.push_str(&String::from_utf8_lossy(&data[1..])),
+                    Some(PROCESS_EXIT) => panic!("connection ended instead of restarting: {:?}", output),
+                    _ => {}
+                }
+            }
+            if pids(&output).len() >= 2 {
+                return;
+            }
+        }
+        panic!("connection closed: {:?}", output);
+    };
+    tokio::time::timeout(Duration::from_secs(10), read).await.expect("no restart seen");
+
+    assert!(output.contains("[process exited with code 3, restarted]"), "output was {:?}", output);
+    let pids = pids(&output);
+    assert_ne!(pids[0], pids[1]);
+}
@@ -0,0 +1,214 @@
+//! 用模拟 PTY 驱动 websocket 处理逻辑的端到端测试，需要 `cargo test --features mock-pty`
+#![cfg(feature = "mock-pty")]
+
+use futures::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use ttyd_rust::protocol::{INPUT, OUTPUT, PTY_READY, SET_WINDOW_TITLE};
+use ttyd_rust::{serve_listener, Config, ConfigBuilder, NewlineMode};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// 可写、不合并输出的模拟 PTY 服务端配置
+fn mock_config() -> ConfigBuilder {
+    Config::builder()
+        .command(vec!["mock".to_string()])
+        .writable(true)
+        .coalesce_ms(0)
+}
+
+async fn start_server() -> SocketAddr {
+    start_server_with(mock_config().build().unwrap()).await
+}
+
+async fn start_server_with(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_listener(listener, config, std::future::pending()));
+    addr
+}
+
+/// 连上 /ws 并发送 Init，等到 PTY 就绪
+async fn connect(addr: SocketAddr, query: &str) -> Client {
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws{}", addr, query))
+        .await
+        .unwrap();
+    let init = br#"{"columns":80,"rows":24}"#.to_vec();
+    ws.send(Message::Binary(init)).await.unwrap();
+    next_message(&mut ws, PTY_READY).await;
+    ws
+}
+
+async fn send_input(ws: &mut Client, text: &str) {
+    let mut msg = vec![INPUT as u8];
+    msg.extend_from_slice(text.as_bytes());
+    ws.send(Message::Binary(msg)).await.unwrap();
+}
+
+/// 读到以 cmd 开头的消息为止，返回去掉命令字节后的负载
+async fn next_message(ws: &mut Client, cmd: char) -> Vec<u8> {
+    let read = async {
+        while let Some(msg) = ws.next().await {
+            if let Message::Binary(data) = msg.unwrap() {
+                if data.first() == Some(&(cmd as u8)) {
+                    return data[1..].to_vec();
+                }
+            }
+        }
+        panic!("connection closed before a {:?} message", cmd);
+    };
+    tokio::time::timeout(Duration::from_secs(5), read).await.expect("timed out")
+}
+
+/// 持续读取输出，直到累计的内容里出现 expected
+async fn read_output_until(ws: &mut Client, expected: &str) -> String {
+    let mut output = String::new();
+    while !output.contains(expected) {
+        output.push_str(&String::from_utf8_lossy(&next_message(ws, OUTPUT).await));
+    }
+    output
+}
+
+#[tokio::test]
+async fn input_is_echoed_back_as_output() {
+    let addr = start_server().await;
+    let mut ws = connect(addr, "").await;
+
+    send_input(&mut ws, "hello").await;
+    read_output_until(&mut ws, "hello").await;
+}
+
+#[tokio::test]
+async fn reattaching_driver_gets_history_and_keeps_the_pty() {
+    let addr = start_server().await;
+    let mut ws = connect(addr, "?session=demo").await;
+    send_input(&mut ws, "before").await;
+    read_output_until(&mut ws, "before").await;
+    ws.close(None).await.unwrap();
+    drop(ws);
+    // 等服务端处理完断开，把 PTY 暂存起来
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut ws = connect(addr, "?session=demo").await;
+    let history = String::from_utf8_lossy(&next_message(&mut ws, OUTPUT).await).into_owned();
+    assert!(history.contains("before"), "history was {:?}", history);
+
+    // 重新连上的是驱动者，输入仍然写进原来的 PTY
+    send_input(&mut ws, "after").await;
+    read_output_until(&mut ws, "after").await;
+}
+
+#[tokio::test]
+async fn viewers_see_the_driver_output_but_cannot_type() {
+    let addr = start_server().await;
+    let mut driver = connect(addr, "?session=shared").await;
+    send_input(&mut driver, "one").await;
+    read_output_until(&mut driver, "one").await;
+
+    let mut viewers = Vec::new();
+    for _ in 0..2 {
+        let mut viewer = connect(addr, "?session=shared").await;
+        let history = String::from_utf8_lossy(&next_message(&mut viewer, OUTPUT).await).into_owned();
+        assert!(history.contains("one"), "history was {:?}", history);
+        viewers.push(viewer);
+    }
+
+    send_input(&mut viewers[0], "ignored-a").await;
+    send_input(&mut viewers[1], "ignored-b").await;
+    send_input(&mut driver, "two").await;
+    for viewer in &mut viewers {
+        let seen = read_output_until(viewer, "two").await;
+        assert!(!seen.contains("ignored"), "viewer saw {:?}", seen);
+    }
+    let echoed = read_output_until(&mut driver, "two").await;
+    assert!(!echoed.contains("ignored"), "driver saw {:?}", echoed);
+}
+
+#[tokio::test]
+async fn crlf_option_controls_what_enter_writes_to_the_pty() {
+    for (mode, expected) in [
+        (NewlineMode::Auto, "<\r>"),
+        (NewlineMode::Cr, "<\r>"),
+        (NewlineMode::Lf, "<\n>"),
+        (NewlineMode::Crlf, "<\r\n>"),
+    ] {
+        let addr = start_server_with(mock_config().newline(mode).build().unwrap()).await;
+        let mut ws = connect(addr, "").await;
+        // 模拟 PTY 原样回显写入的字节
+        send_input(&mut ws, "<\r>").await;
+        let output = read_output_until(&mut ws, ">").await;
+        assert!(output.contains(expected), "{:?}: {:?}", mode, output);
+    }
+}
+
+#[tokio::test]
+async fn osc_title_in_the_output_updates_the_window_title() {
+    let addr = start_server().await;
+    let mut ws = connect(addr, "").await;
+
+    // 模拟 PTY 把输入原样回显，相当于程序输出了设置标题的序列
+    send_input(&mut ws, "\x1b]0;foo\x07").await;
+    let read = async {
+        loop {
+            if next_message(&mut ws, SET_WINDOW_TITLE).await == b"foo" {
+                return;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), read).await.expect("title was not forwarded");
+}
+
+#[tokio::test]
+async fn ready_is_sent_once_the_pty_is_spawned_and_before_any_output() {
+    let addr = start_server().await;
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+    ws.send(Message::Binary(br#"{"columns":80,"rows":24}"#.to_vec())).await.unwrap();
+
+    // Ready 之前不能出现输出帧，否则客户端会提前清掉 "Connecting…"
+    let read = async {
+        while let Some(msg) = ws.next().await {
+            if let Message::Binary(data) = msg.unwrap() {
+                match data.first().map(|&b| b as char) {
+                    Some(PTY_READY) => return,
+                    Some(OUTPUT) => panic!("output {:?} arrived before ready", data),
+                    _ => {}
+                }
+            }
+        }
+        panic!("connection closed before the ready signal");
+    };
+    tokio::time::timeout(Duration::from_secs(5), read).await.expect("timed out");
+
+    // 就绪之后 PTY 确实可用
+    send_input(&mut ws, "ready").await;
+    read_output_until(&mut ws, "ready").await;
+}
+
+#[tokio::test]
+async fn oversized_frames_close_the_connection() {
+    let addr = start_server_with(mock_config().max_message_size(1024).build().unwrap()).await;
+    let mut ws = connect(addr, "").await;
+
+    let mut msg = vec![INPUT as u8];
+    msg.extend(vec![b'x'; 64 * 1024]);
+    // 服务端可能在读完之前就断开，发送失败也算预期结果
+    let _ = ws.send(Message::Binary(msg)).await;
+
+    let read = async {
+        loop {
+            match ws.next().await {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => return,
+                Some(Ok(Message::Binary(data))) => {
+                    assert!(!String::from_utf8_lossy(&data).contains("xxxx"), "oversized input reached the PTY");
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), read).await.expect("connection stayed open");
+}
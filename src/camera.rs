@@ -2,6 +2,7 @@ use crate::vec2::Vec2;
 use crate::world::World;
 use std::f64::consts::PI;
 
+#[derive(Clone)]
 pub struct Camera {
     pub position: Vec2,
     pub direction: Vec2,
@@ -12,6 +13,8 @@ pub struct Camera {
     pub z_position: f64,
     pub z_velocity: f64,
     pub bob_phase: f64,
+    /// 穿墙模式：移动时不做碰撞检测，只限制在地图范围内
+    pub noclip: bool,
 }
 
 impl Camera {
@@ -29,19 +32,37 @@ impl Camera {
             z_position: 0.0,
             z_velocity: 0.0,
             bob_phase: 0.0,
+            noclip: false,
         }
     }
 
-    pub fn move_forward(&mut self, world: &World, delta: f64) {
-        let new_pos = self.position + self.direction * (self.move_speed * delta);
-        if !world.is_wall(new_pos.x as i32, self.position.y as i32) {
-            self.position.x = new_pos.x;
+    /// 按位移移动，x、y 两个方向分别做碰撞检测，撞墙时沿墙滑动
+    fn slide(&mut self, world: &World, offset: Vec2) {
+        if self.noclip {
+            self.position += offset;
+            self.position.x = self.position.x.clamp(0.5, world.width as f64 - 0.5);
+            self.position.y = self.position.y.clamp(0.5, world.height as f64 - 0.5);
+            self.bob_phase += 0.2;
+            return;
+        }
+
+        let mut step = offset;
+        if world.is_solid_at(Vec2::new(self.position.x + step.x, self.position.y)) {
+            step.x = 0.0;
         }
-        if !world.is_wall(self.position.x as i32, new_pos.y as i32) {
-            self.position.y = new_pos.y;
+        if world.is_solid_at(self.position + step) {
+            step.y = 0.0;
         }
-        
+        self.position += step;
         self.bob_phase += 0.2;
+    }
+
+    fn right(&self) -> Vec2 {
+        Vec2::new(self.direction.y, -self.direction.x)
+    }
+
+    pub fn move_forward(&mut self, world: &World, delta: f64) {
+        self.slide(world, self.direction * (self.move_speed * delta));
         
         if self.pitch > 0.1 {
             self.z_velocity += 0.05;
@@ -49,41 +70,15 @@ impl Camera {
     }
 
     pub fn move_backward(&mut self, world: &World, delta: f64) {
-        let new_pos = self.position - self.direction * (self.move_speed * delta);
-        if !world.is_wall(new_pos.x as i32, self.position.y as i32) {
-            self.position.x = new_pos.x;
-        }
-        if !world.is_wall(self.position.x as i32, new_pos.y as i32) {
-            self.position.y = new_pos.y;
-        }
-        
-        self.bob_phase += 0.2;
+        self.slide(world, -self.direction * (self.move_speed * delta));
     }
 
     pub fn strafe_left(&mut self, world: &World, delta: f64) {
-        let right = Vec2::new(self.direction.y, -self.direction.x);
-        let new_pos = self.position - right * (self.move_speed * delta);
-        if !world.is_wall(new_pos.x as i32, self.position.y as i32) {
-            self.position.x = new_pos.x;
-        }
-        if !world.is_wall(self.position.x as i32, new_pos.y as i32) {
-            self.position.y = new_pos.y;
-        }
-        
-        self.bob_phase += 0.2;
+        self.slide(world, -self.right() * (self.move_speed * delta));
     }
 
     pub fn strafe_right(&mut self, world: &World, delta: f64) {
-        let right = Vec2::new(self.direction.y, -self.direction.x);
-        let new_pos = self.position + right * (self.move_speed * delta);
-        if !world.is_wall(new_pos.x as i32, self.position.y as i32) {
-            self.position.x = new_pos.x;
-        }
-        if !world.is_wall(self.position.x as i32, new_pos.y as i32) {
-            self.position.y = new_pos.y;
-        }
-        
-        self.bob_phase += 0.2;
+        self.slide(world, self.right() * (self.move_speed * delta));
     }
 
     pub fn rotate(&mut self, angle: f64) {
@@ -97,6 +92,23 @@ impl Camera {
         self.plane = self.plane.rotate(angle);
     }
 
+    /// 朝 target 转向，每次最多转 max_turn 弧度并总是走较短的方向；
+    /// 返回转完之后还差的角度
+    pub fn face_point(&mut self, target: Vec2, max_turn: f64) -> f64 {
+        let wanted = (target - self.position).angle();
+        let mut diff = wanted - self.direction.angle();
+        // 归一化到 (-PI, PI]，保证转向较短的一侧
+        while diff > PI {
+            diff -= 2.0 * PI;
+        }
+        while diff <= -PI {
+            diff += 2.0 * PI;
+        }
+        let turn = diff.clamp(-max_turn, max_turn);
+        self.rotate_absolute(turn);
+        diff - turn
+    }
+
     pub fn look_up(&mut self, delta: f64) {
         self.pitch = (self.pitch + delta * 0.05).clamp(-PI / 3.0, PI / 3.0);
     }
@@ -105,16 +117,70 @@ impl Camera {
         self.pitch = (self.pitch - delta * 0.05).clamp(-PI / 3.0, PI / 3.0);
     }
 
-    pub fn update(&mut self, _delta_time: f64) {
-        self.z_velocity -= 0.02;
-        self.z_position += self.z_velocity;
+    /// 推进跳跃物理；各常数按每秒 30 步标定，帧时间不同时按比例缩放
+    pub fn update(&mut self, delta_time: f64) {
+        let steps = delta_time * 30.0;
+        self.z_velocity -= 0.02 * steps;
+        self.z_position += self.z_velocity * steps;
         
         if self.z_position < 0.0 {
             self.z_position = 0.0;
             self.z_velocity = 0.0;
         }
         
-        self.z_velocity *= 0.95;
+        self.z_velocity *= 0.95f64.powf(steps);
+    }
+
+    /// 玩家正前方相邻的格子（取方向的主轴，保证是上下左右之一）
+    pub fn facing_cell(&self) -> (i32, i32) {
+        let cell_x = self.position.x.floor() as i32;
+        let cell_y = self.position.y.floor() as i32;
+        if self.direction.x.abs() >= self.direction.y.abs() {
+            (cell_x + self.direction.x.signum() as i32, cell_y)
+        } else {
+            (cell_x, cell_y + self.direction.y.signum() as i32)
+        }
+    }
+
+    /// 在视野范围内均匀投射 rays 条射线，返回射线撞墙前经过的所有空格
+    pub fn visible_cells(&self, world: &World, rays: usize) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        let max_iterations = world.width + world.height;
+
+        for i in 0..rays {
+            let camera_x = if rays > 1 { 2.0 * i as f64 / (rays - 1) as f64 - 1.0 } else { 0.0 };
+            let ray = self.direction + self.plane * camera_x;
+
+            let mut map_x = self.position.x as i32;
+            let mut map_y = self.position.y as i32;
+            let delta_x = if ray.x.abs() < 1e-10 { 1e30 } else { (1.0 / ray.x).abs() };
+            let delta_y = if ray.y.abs() < 1e-10 { 1e30 } else { (1.0 / ray.y).abs() };
+            let (step_x, mut side_x) = if ray.x < 0.0 {
+                (-1, (self.position.x - map_x as f64) * delta_x)
+            } else {
+                (1, (map_x as f64 + 1.0 - self.position.x) * delta_x)
+            };
+            let (step_y, mut side_y) = if ray.y < 0.0 {
+                (-1, (self.position.y - map_y as f64) * delta_y)
+            } else {
+                (1, (map_y as f64 + 1.0 - self.position.y) * delta_y)
+            };
+
+            for _ in 0..max_iterations {
+                if world.is_wall(map_x, map_y) {
+                    break;
+                }
+                cells.push((map_x as usize, map_y as usize));
+                if side_x < side_y {
+                    side_x += delta_x;
+                    map_x += step_x;
+                } else {
+                    side_y += delta_y;
+                    map_y += step_y;
+                }
+            }
+        }
+        cells
     }
 
     pub fn get_view_bob(&self) -> f64 {
@@ -129,3 +195,98 @@ impl Camera {
         base_offset + bob_offset + jump_offset
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze_gen::{MAP_HEIGHT, MAP_WIDTH};
+    use crate::world::WallType;
+
+    /// 一间 4x4 的空房间（x、y 都在 1..=4），四周是墙
+    fn room() -> World {
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for column in map.iter_mut().skip(1).take(4) {
+            for cell in column.iter_mut().skip(1).take(4) {
+                *cell = WallType::Empty;
+            }
+        }
+        World::from_map(map, (2.5, 2.5))
+    }
+
+    #[test]
+    fn moving_diagonally_into_a_wall_slides_along_it() {
+        let world = room();
+        let mut camera = Camera::new(Vec2::new(1.05, 2.5), Vec2::new(-1.0, 1.0));
+        camera.move_forward(&world, 1.0);
+        // x 方向被西墙挡住，y 方向照常前进
+        assert_eq!(camera.position.x, 1.05);
+        assert!(camera.position.y > 2.5);
+
+        let before = camera.position;
+        camera.move_backward(&world, 1.0);
+        camera.strafe_left(&world, 1.0);
+        camera.strafe_right(&world, 1.0);
+        assert!((camera.position - before).magnitude() < 0.2);
+    }
+
+    #[test]
+    fn walking_up_to_a_t_junction_reveals_both_arms() {
+        // 沿 y = 5 向东的走廊，在 x = 5 接上一条南北向的走廊
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for column in map.iter_mut().skip(1).take(4) {
+            column[5] = WallType::Empty;
+        }
+        for cell in map[5].iter_mut().skip(1).take(9) {
+            *cell = WallType::Empty;
+        }
+        let world = World::from_map(map, (1.5, 5.5));
+
+        let mut camera = Camera::new(Vec2::new(1.5, 5.5), Vec2::new(1.0, 0.0));
+        let mut revealed = std::collections::HashSet::new();
+        while camera.position.x < 4.5 {
+            revealed.extend(camera.visible_cells(&world, 32));
+            camera.move_forward(&world, 1.0);
+        }
+
+        assert!(revealed.iter().all(|&(x, y)| map[x][y] == WallType::Empty));
+        assert!(revealed.contains(&(5, 5)));
+        assert!(revealed.iter().any(|&(x, y)| x == 5 && y < 5), "north arm hidden");
+        assert!(revealed.iter().any(|&(x, y)| x == 5 && y > 5), "south arm hidden");
+    }
+
+    #[test]
+    fn noclip_moves_into_wall_cells() {
+        let world = room();
+        let mut camera = Camera::new(Vec2::new(4.9, 2.5), Vec2::new(1.0, 0.0));
+        camera.move_forward(&world, 1.0);
+        assert_eq!(camera.position.x, 4.9);
+
+        camera.noclip = true;
+        camera.move_forward(&world, 1.0);
+        assert!(camera.position.x > 5.0);
+        assert!(world.is_wall(camera.position.x as i32, camera.position.y as i32));
+    }
+
+    #[test]
+    fn face_point_turns_the_short_way_without_overshooting() {
+        // 朝 -x（角度 PI），目标在略偏 -y 的方向（角度接近 -PI）：
+        // 直接相减会得到接近 -2PI 的差，应该跨过 ±PI 往正方向转一小段
+        let mut camera = Camera::new(Vec2::new(0.0, 0.0), Vec2::new(-1.0, 0.0));
+        let target = Vec2::new(-1.0, -0.2);
+        let wanted = target.angle();
+
+        let start = camera.direction.angle();
+        let remaining = camera.face_point(target, 0.05);
+        let turned = camera.direction.angle() - start;
+        let turned = if turned > PI { turned - 2.0 * PI } else if turned < -PI { turned + 2.0 * PI } else { turned };
+        assert!((turned.abs() - 0.05).abs() < 1e-9);
+        assert!(turned > 0.0, "turned the long way: {}", turned);
+        assert!(remaining.abs() < PI / 2.0);
+
+        // 一次允许转很大角度时正好停在目标方向上，镜头平面保持垂直
+        let remaining = camera.face_point(target, 1.0);
+        assert!(remaining.abs() < 1e-9);
+        assert!((camera.direction.angle() - wanted).abs() < 1e-9);
+        assert!(camera.direction.dot(&camera.plane).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,145 @@
+use clap::ValueEnum;
+use ratatui::style::Color;
+
+/// 终端的颜色能力，渲染时把 RGB 颜色降级到终端支持的调色板
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum ColorMode {
+    /// 根据 NO_COLOR / COLORTERM / TERM 自动判断
+    Auto,
+    #[value(name = "16")]
+    Ansi16,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "truecolor")]
+    TrueColor,
+    Off,
+}
+
+// 标准 16 色及其常见的 RGB 值（xterm 默认配色）
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// 256 色中 6x6x6 色块每一级对应的分量值
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorMode {
+    /// 把 Auto 解析成具体模式，其余模式原样返回
+    pub fn resolve(self) -> ColorMode {
+        if self != ColorMode::Auto {
+            return self;
+        }
+
+        if std::env::var_os("NO_COLOR").is_some() {
+            return ColorMode::Off;
+        }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+            return ColorMode::TrueColor;
+        }
+        let term = std::env::var("TERM").unwrap_or_default().to_lowercase();
+        if term == "dumb" {
+            ColorMode::Off
+        } else if term.contains("256color") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+
+    /// 按当前模式转换颜色，只改写 RGB 颜色
+    pub fn apply(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorMode::Off, _) => Color::Reset,
+            (ColorMode::Ansi16, Color::Rgb(r, g, b)) => quantize_16(r, g, b),
+            (ColorMode::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(quantize_256(r, g, b)),
+            _ => color,
+        }
+    }
+}
+
+fn distance_sq(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    dr * dr + dg * dg + db * db
+}
+
+/// 找出 16 色中与 (r, g, b) 欧氏距离最近的颜色
+pub fn quantize_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance_sq(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::White)
+}
+
+/// 在 256 色的 6x6x6 色块和 24 级灰阶中取最近的索引
+pub fn quantize_256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |v: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (**level as i32 - v as i32).abs())
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube_rgb = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // 灰阶 232..=255 对应 8, 18, ..., 238
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = ((avg as i32 - 8).max(0) / 10).min(23) as u8;
+    let gray_value = 8 + gray_step * 10;
+    let gray_index = 232 + gray_step;
+
+    if distance_sq((gray_value, gray_value, gray_value), (r, g, b)) < distance_sq(cube_rgb, (r, g, b)) {
+        gray_index
+    } else {
+        cube_index as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantize_16_picks_the_nearest_ansi_color() {
+        assert_eq!(quantize_16(0, 0, 0), Color::Black);
+        assert_eq!(quantize_16(250, 10, 10), Color::LightRed);
+        assert_eq!(quantize_16(0, 190, 200), Color::Cyan);
+        assert_eq!(quantize_16(130, 125, 120), Color::DarkGray);
+    }
+
+    #[test]
+    fn quantize_256_uses_the_cube_and_the_gray_ramp() {
+        assert_eq!(quantize_256(255, 0, 0), 196);
+        assert_eq!(quantize_256(0, 0, 0), 16);
+        assert_eq!(quantize_256(128, 128, 128), 244);
+    }
+
+    #[test]
+    fn apply_only_rewrites_rgb_colors() {
+        assert_eq!(ColorMode::Ansi16.apply(Color::Rgb(255, 255, 255)), Color::White);
+        assert_eq!(ColorMode::Ansi16.apply(Color::Yellow), Color::Yellow);
+        assert_eq!(ColorMode::TrueColor.apply(Color::Rgb(1, 2, 3)), Color::Rgb(1, 2, 3));
+        assert_eq!(ColorMode::Off.apply(Color::Yellow), Color::Reset);
+    }
+}
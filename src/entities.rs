@@ -1,4 +1,4 @@
-use crate::world::WallType;
+use crate::world::{WallType, World};
 use rand::Rng;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -8,6 +8,8 @@ pub enum ItemType {
     Key,
     Health,
     Exit,
+    Radar,
+    Magnet,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -35,6 +37,8 @@ impl Item {
             ItemType::Key => '🔑',
             ItemType::Health => '❤',
             ItemType::Exit => '🚪',
+            ItemType::Radar => '📡',
+            ItemType::Magnet => '🧲',
         }
     }
 
@@ -54,6 +58,10 @@ pub struct NPC {
     pub dir_y: f64,
     pub npc_type: NPCType,
     pub animation_phase: f64,
+    // 守卫的视野：距离和半张角（弧度），发现玩家后进入警戒
+    pub vision_range: f64,
+    pub vision_half_angle: f64,
+    pub alert: bool,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -64,9 +72,13 @@ pub enum NPCType {
 
 impl NPC {
     pub fn new(x: f64, y: f64, npc_type: NPCType) -> Self {
-        let mut rng = rand::thread_rng();
+        Self::with_rng(x, y, npc_type, &mut rand::thread_rng())
+    }
+
+    /// 用给定的随机数生成器决定初始朝向，便于按种子复现
+    pub fn with_rng(x: f64, y: f64, npc_type: NPCType, rng: &mut impl Rng) -> Self {
         let angle = rng.gen_range(0.0..std::f64::consts::PI * 2.0);
-        
+
         NPC {
             x,
             y,
@@ -74,16 +86,65 @@ impl NPC {
             dir_y: angle.sin(),
             npc_type,
             animation_phase: 0.0,
+            vision_range: 6.0,
+            vision_half_angle: std::f64::consts::PI / 4.0,
+            alert: false,
+        }
+    }
+
+    /// 守卫能否看见 (px, py)：在视距内、在朝向的视锥内且视线不被墙挡住
+    pub fn can_see(&self, px: f64, py: f64, world: &World) -> bool {
+        if self.npc_type != NPCType::Guard {
+            return false;
+        }
+
+        let dx = px - self.x;
+        let dy = py - self.y;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist > self.vision_range {
+            return false;
+        }
+        if dist > 1e-6 {
+            let heading = (self.dir_x * self.dir_x + self.dir_y * self.dir_y).sqrt().max(1e-6);
+            let cos_angle = (dx * self.dir_x + dy * self.dir_y) / (dist * heading);
+            if cos_angle < self.vision_half_angle.cos() {
+                return false;
+            }
+        }
+
+        world.line_of_sight((self.x, self.y), (px, py))
+    }
+
+    /// 发现玩家时进入警戒并转向玩家，丢失目标后解除警戒
+    pub fn update_vision(&mut self, px: f64, py: f64, world: &World) {
+        if self.can_see(px, py, world) {
+            self.alert = true;
+            let dx = px - self.x;
+            let dy = py - self.y;
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist > 1e-6 {
+                self.dir_x = dx / dist;
+                self.dir_y = dy / dist;
+            }
+        } else {
+            self.alert = false;
         }
     }
 
     pub fn update(&mut self, world_map: &[[WallType; crate::maze_gen::MAP_HEIGHT]; crate::maze_gen::MAP_WIDTH], delta_time: f64) {
+        self.update_with_rng(world_map, delta_time, &mut rand::thread_rng());
+    }
+
+    /// 随机转向使用给定的随机数生成器，便于回放时复现NPC的行动
+    pub fn update_with_rng(&mut self, world_map: &[[WallType; crate::maze_gen::MAP_HEIGHT]; crate::maze_gen::MAP_WIDTH], delta_time: f64, rng: &mut impl Rng) {
         self.animation_phase += delta_time * 3.0;
         
+        // 速度按每秒 30 次更新标定
+        let steps = delta_time * 30.0;
         let speed = match self.npc_type {
             NPCType::Wanderer => 0.02,
             NPCType::Guard => 0.01,
-        };
+        } * steps;
 
         let new_x = self.x + self.dir_x * speed;
         let new_y = self.y + self.dir_y * speed;
@@ -100,8 +161,8 @@ impl NPC {
             self.dir_y = -self.dir_y;
         }
 
-        if rand::thread_rng().gen_range(0..100) < 2 {
-            let angle = rand::thread_rng().gen_range(0.0..std::f64::consts::PI * 2.0);
+        if rng.gen_bool((0.02 * steps).min(1.0)) {
+            let angle = rng.gen_range(0.0..std::f64::consts::PI * 2.0);
             self.dir_x = angle.cos();
             self.dir_y = angle.sin();
         }
@@ -109,17 +170,9 @@ impl NPC {
 
     #[allow(dead_code)]
     pub fn get_sprite(&self) -> char {
-        let phase = (self.animation_phase % 2.0) / 2.0;
         match self.npc_type {
-            NPCType::Wanderer => {
-                if phase < 0.25 { '🚶' }
-                else if phase < 0.5 { '🚶' }
-                else if phase < 0.75 { '🚶' }
-                else { '🚶' }
-            }
-            NPCType::Guard => {
-                if phase < 0.5 { '💂' } else { '💂' }
-            }
+            NPCType::Wanderer => '🚶',
+            NPCType::Guard => '💂',
         }
     }
 
@@ -130,3 +183,68 @@ impl NPC {
         (dx * dx + dy * dy).sqrt()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze_gen::{MAP_HEIGHT, MAP_WIDTH};
+
+    /// 一间 8x8 的空房间，x = 5 处有一堵从 y = 1 到 y = 4 的墙
+    fn room() -> World {
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for column in map.iter_mut().skip(1).take(8) {
+            for cell in column.iter_mut().skip(1).take(8) {
+                *cell = WallType::Empty;
+            }
+        }
+        for cell in map[5].iter_mut().skip(1).take(4) {
+            *cell = WallType::Red;
+        }
+        World::from_map(map, (1.5, 1.5))
+    }
+
+    /// 在 (x, y) 朝 +x 站着的守卫
+    fn guard(x: f64, y: f64) -> NPC {
+        NPC {
+            dir_x: 1.0,
+            dir_y: 0.0,
+            ..NPC::new(x, y, NPCType::Guard)
+        }
+    }
+
+    #[test]
+    fn guard_sees_only_inside_its_cone() {
+        let world = room();
+        let guard = guard(1.5, 6.5);
+        assert!(guard.can_see(4.5, 6.5, &world));
+        assert!(guard.can_see(4.5, 7.5, &world));
+        // 身后和正侧方都在视锥外
+        assert!(!guard.can_see(1.5, 8.5, &world));
+        let behind = NPC { dir_x: -1.0, ..guard };
+        assert!(!behind.can_see(4.5, 6.5, &world));
+        // 超出视距
+        let short_sighted = NPC { vision_range: 2.0, ..guard };
+        assert!(!short_sighted.can_see(4.5, 6.5, &world));
+    }
+
+    #[test]
+    fn walls_block_the_guard_and_wanderers_never_see() {
+        let world = room();
+        assert!(!guard(2.5, 2.5).can_see(7.5, 2.5, &world));
+
+        let wanderer = NPC { dir_x: 1.0, dir_y: 0.0, ..NPC::new(1.5, 6.5, NPCType::Wanderer) };
+        assert!(!wanderer.can_see(3.5, 6.5, &world));
+    }
+
+    #[test]
+    fn spotting_the_player_raises_the_alert_and_turns_the_guard() {
+        let world = room();
+        let mut guard = guard(1.5, 6.5);
+        guard.update_vision(4.5, 7.5, &world);
+        assert!(guard.alert);
+        assert!(guard.dir_y > 0.0);
+
+        guard.update_vision(7.5, 2.5, &world);
+        assert!(!guard.alert);
+    }
+}
@@ -0,0 +1,10 @@
+/// 游戏过程中发生的事件，和渲染解耦，供以后的音效、震动等功能订阅
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    Footstep,
+    CoinPickup,
+    KeyPickup,
+    /// 受到的伤害值
+    Damage(f64),
+    Jump,
+}
@@ -0,0 +1,21 @@
+//! 终端里的 ASCII 光线投射迷宫，可以作为库嵌入到其他程序中
+
+pub mod camera;
+pub mod color;
+pub mod entities;
+pub mod events;
+pub mod maze_gen;
+pub mod renderer;
+pub mod stats;
+pub mod vec2;
+pub mod world;
+
+pub use camera::Camera;
+pub use color::ColorMode;
+pub use entities::{Item, ItemType, NPCType, NPC};
+pub use events::GameEvent;
+pub use maze_gen::{MazeGenerator, MAP_HEIGHT, MAP_WIDTH};
+pub use renderer::{CeilingStyle, RenderContext, Renderer, WallTexture};
+pub use stats::{PlayerStats, MAX_HEALTH};
+pub use vec2::Vec2;
+pub use world::{Interaction, MazeStats, RayHit, TileInfo, WallType, World};
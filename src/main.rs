@@ -9,29 +9,192 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, BorderType, Paragraph, Gauge},
-    Terminal,
+    Frame, Terminal,
 };
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
 use std::io;
 use std::time::{Duration, Instant};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 
-mod vec2;
-mod world;
-mod camera;
-mod renderer;
-mod maze_gen;
-mod entities;
+mod replay;
 
-use vec2::Vec2;
-use world::World;
-use camera::Camera;
-use renderer::Renderer;
-use entities::{Item, ItemType, NPC, NPCType};
+use replay::{Action, Recording, ReplayEntry};
+
+use arsvt3d::{Camera, ColorMode, GameEvent, Item, ItemType, NPCType, PlayerStats, RenderContext, Renderer, Vec2, World, NPC};
+
+// 每走一步踩在陷阱上扣除的生命值
+const TRAP_DAMAGE: f64 = 5.0;
+
+// 冲刺：耐力上限、每秒消耗和恢复量、速度倍数，
+// 以及每次按下冲刺键后保持冲刺的时间（终端只能收到按键重复，收不到"按住"）
+const MAX_STAMINA: f64 = 100.0;
+const SPRINT_DRAIN: f64 = 40.0;
+const STAMINA_REGEN: f64 = 15.0;
+const SPRINT_MULTIPLIER: f64 = 1.8;
+const SPRINT_HOLD: f64 = 0.2;
+
+const RADAR_DURATION: f64 = 20.0;
+const RADAR_RADIUS: f64 = 15.0;
+
+// 拾取半径；吃到磁铁后在一段时间内扩大
+const PICKUP_RADIUS: f64 = 0.6;
+const MAGNET_PICKUP_RADIUS: f64 = 1.5;
+const MAGNET_DURATION: f64 = 15.0;
+
+// 每帧用于揭示小地图的视线条数
+const SIGHT_RAYS: usize = 64;
+
+// 达到探索目标时奖励的金币
+const EXPLORE_BONUS_COINS: u32 = 5;
+
+// 同时存在的NPC上限
+const MAX_NPCS: usize = 8;
 
 const TARGET_FPS: u64 = 60;
-const FRAME_TIME: Duration = Duration::from_millis(1000 / TARGET_FPS);
+const MIN_FPS: u64 = 5;
+const MAX_FPS: u64 = 120;
+const FPS_STEP: u64 = 5;
+
+const MAX_DELTA_TIME: f64 = 0.1;
+
+// 按住屏幕上的移动按钮：0.3 秒后开始连发，之后每 0.1 秒一次。
+// 每次按钮动作走过的距离按 30 帧每秒走满一个连发间隔来算，按住时的速度和自动行走一致
+const BUTTON_REPEAT_DELAY: f64 = 0.3;
+const BUTTON_REPEAT_INTERVAL: f64 = 0.1;
+const BUTTON_STEP: f64 = BUTTON_REPEAT_INTERVAL * 30.0;
+
+fn frame_time(target_fps: u64) -> Duration {
+    Duration::from_millis(1000 / target_fps.clamp(MIN_FPS, MAX_FPS))
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(name = "arsvt3d")]
+#[command(about = "ASCII raycasting maze in your terminal", long_about = None)]
+struct Args {
+    /// 迷宫种子，相同的种子生成相同的迷宫
+    #[arg(short, long)]
+    seed: Option<u64>,
+
+    #[arg(short, long, value_enum, default_value = "normal")]
+    difficulty: Difficulty,
+
+    /// 颜色模式，auto 时根据 COLORTERM / TERM 判断
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorMode,
+
+    /// 前方墙壁近于该距离时显示 "WALL!" 警告
+    #[arg(long, default_value = "1.5")]
+    wall_near: f64,
+
+    /// 前方墙壁近于该距离时显示 "Close" 提示
+    #[arg(long, default_value = "3.0")]
+    wall_far: f64,
+
+    /// NPC不会出生在离起点或玩家这么近的地方（格）
+    #[arg(long, default_value = "8.0")]
+    npc_safe_radius: f64,
+
+    /// 用半块字符绘制墙的上下边缘（部分终端显示效果不好）
+    #[arg(long)]
+    smooth_edges: bool,
+
+    /// 出口解锁前需要探索的空格百分比，0 表示不要求
+    #[arg(long, default_value = "0")]
+    explore_goal: f64,
+
+    /// 只输出 ASCII 字符；locale 不是 UTF-8 时会自动开启
+    #[arg(long)]
+    ascii: bool,
+
+    /// 把本局的操作录制到该文件，退出时写入（未指定种子时随机选一个）
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<String>,
+
+    /// 不打开界面，按录像文件重放整局并输出最终状态
+    #[arg(long)]
+    replay: Option<String>,
+
+    /// 不打开界面，生成迷宫后把地图、起点、物品和NPC位置以 JSON 输出到标准输出
+    #[arg(long, conflicts_with_all = ["record", "replay"])]
+    dump_state: bool,
+
+    /// 无操作多少秒后进入演示模式，镜头自动在迷宫里游走；0 表示关闭
+    #[arg(long, default_value = "60")]
+    demo_after: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum WallProximity {
+    Danger,
+    Close,
+    Clear,
+}
+
+/// 按两个阈值给前方墙壁的距离分级
+fn wall_proximity_level(dist: f64, near: f64, far: f64) -> WallProximity {
+    if dist < near {
+        WallProximity::Danger
+    } else if dist < far {
+        WallProximity::Close
+    } else {
+        WallProximity::Clear
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn coin_count(self) -> usize {
+        match self {
+            Difficulty::Easy => 12,
+            Difficulty::Normal => 8,
+            Difficulty::Hard => 5,
+        }
+    }
+
+    fn npc_types(self) -> &'static [NPCType] {
+        match self {
+            Difficulty::Easy => &[NPCType::Wanderer],
+            Difficulty::Normal => &[NPCType::Wanderer, NPCType::Guard],
+            Difficulty::Hard => &[NPCType::Wanderer, NPCType::Guard, NPCType::Guard],
+        }
+    }
+
+    /// 打开出口前必须找到的钥匙数
+    fn key_count(self) -> usize {
+        match self {
+            Difficulty::Easy => 2,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 4,
+        }
+    }
+
+    fn radar_chance(self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.0,
+            Difficulty::Normal => 0.5,
+            Difficulty::Hard => 0.0,
+        }
+    }
 
-#[derive(Clone, Copy, PartialEq)]
+    fn magnet_chance(self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.0,
+            Difficulty::Normal => 0.6,
+            Difficulty::Hard => 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Button {
     Forward,
     Backward,
@@ -118,32 +281,82 @@ struct App {
     renderer: Renderer,
     running: bool,
     fps: f64,
+    target_fps: u64,
     buttons: Vec<ButtonState>,
     mouse_dragging: bool,
     last_mouse_pos: Option<(u16, u16)>,
     animation_frame: usize,
-    health: f64,
-    steps: u32,
+    // 金币、钥匙、步数、生命值和分数
+    stats: PlayerStats,
     items: Vec<Item>,
     npcs: Vec<NPC>,
-    coins_collected: u32,
-    keys_collected: u32,
     monochrome_mode: bool,
     energy_bar_rect: Option<Rect>,
     // 添加用于跟踪持续按压的字段
     pressed_button: Option<Button>,
-    button_press_time: Option<Instant>,
+    // 按钮已经按住的时间（秒），每次连发后减去一个连发间隔
+    button_held_time: f64,
     // 添加全屏视角模式相关字段
     fullscreen_mode: bool,
     minimap_rect: Option<Rect>,
+    // 和平模式：不生成也不更新NPC
+    peaceful: bool,
+    // 雷达剩余时间（秒），大于0时小地图标出附近物品
+    radar_time_left: f64,
+    // 当前的拾取半径，磁铁生效期间大于 PICKUP_RADIUS
+    pickup_radius: f64,
+    // 磁铁剩余时间（秒），归零时拾取半径恢复
+    magnet_time_left: f64,
+    difficulty: Difficulty,
+    // 自动寻路时剩余要走的格子，为空表示未开启
+    auto_path: Vec<(usize, usize)>,
+    // 上次按 H 时没找到去出口的路，状态栏提示到玩家再走一步为止
+    auto_walk_no_path: bool,
+    // 玩家看到过的空格，小地图只在这些格子上画出路径
+    visited: Vec<Vec<bool>>,
+    // 墙壁距离提示的两个阈值
+    wall_near: f64,
+    wall_far: f64,
+    // 小地图上是否标出NPC，关闭后只能靠3D视角发现敌人
+    show_npcs_on_minimap: bool,
+    // 3D 视图右上角的后视镜小窗
+    show_rear_view: bool,
+    // 小地图下方用图例代替帮助面板
+    show_minimap_legend: bool,
+    npc_safe_radius: f64,
+    // 出口解锁需要的探索比例（0..1），以及本迷宫是否已发过探索奖励
+    explore_goal: f64,
+    explore_bonus_given: bool,
+    // 冲刺耐力，以及距离冲刺结束还剩的时间
+    stamina: f64,
+    sprint_time_left: f64,
+    // 物品和NPC的摆放用它，指定种子时整局布局都可以复现
+    rng: StdRng,
+    // 本帧发生的游戏事件，每帧结束时取走
+    events: Vec<GameEvent>,
+    // 开启录像时依次记录每一帧和每个操作
+    recording: Option<Recording>,
+    // 演示模式：无操作超过 demo_after 秒后接管镜头，任意输入时恢复进入前保存的玩家镜头
+    demo_after: f64,
+    last_input: Instant,
+    demo_saved_camera: Option<Camera>,
+    demo_path: Vec<(usize, usize)>,
 }
 
 impl App {
-    fn new() -> Self {
-        let world = World::new_random();
+    fn new(args: &Args) -> Self {
+        let difficulty = args.difficulty;
+        let color_mode = args.color.resolve();
+        let world = match args.seed {
+            Some(seed) => World::from_seed(seed),
+            None => World::new_random(),
+        };
         let start_pos = world.get_start_position();
-        let camera = Camera::new(Vec2::new(start_pos.0, start_pos.1), Vec2::new(-1.0, 0.0));
-        let renderer = Renderer::new();
+        let camera = Camera::new(start_pos.into(), Vec2::new(-1.0, 0.0));
+        let mut renderer = Renderer::new();
+        renderer.color_mode = color_mode;
+        renderer.smooth_edges = args.smooth_edges;
+        renderer.ascii_only = args.ascii || !arsvt3d::renderer::unicode_supported();
 
         let buttons = vec![
             ButtonState::new(Button::Forward),
@@ -156,147 +369,256 @@ impl App {
             ButtonState::new(Button::NewMaze),
         ];
 
-        let mut items = Vec::new();
-        let mut npcs = Vec::new();
-        
-        for _ in 0..8 {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..world.width - 5) as f64;
-                let y = rng.gen_range(5..world.height - 5) as f64;
-                if !world.is_wall(x as i32, y as i32) {
-                    items.push(Item::new(x + 0.5, y + 0.5, ItemType::Coin));
-                    break;
-                }
-            }
-        }
-        
-        for _ in 0..2 {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..world.width - 5) as f64;
-                let y = rng.gen_range(5..world.height - 5) as f64;
-                if !world.is_wall(x as i32, y as i32) {
-                    items.push(Item::new(x + 0.5, y + 0.5, ItemType::Key));
-                    break;
-                }
-            }
-        }
-
-        for npc_type in [NPCType::Wanderer, NPCType::Guard] {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..world.width - 5) as f64;
-                let y = rng.gen_range(5..world.height - 5) as f64;
-                if !world.is_wall(x as i32, y as i32) {
-                    npcs.push(NPC::new(x + 0.5, y + 0.5, npc_type));
-                    break;
-                }
-            }
-        }
-
-        App {
+        let mut app = App {
             camera,
             world,
             renderer,
             running: true,
             fps: 0.0,
+            target_fps: TARGET_FPS,
             buttons,
             mouse_dragging: false,
             last_mouse_pos: None,
             animation_frame: 0,
-            health: 100.0,
-            steps: 0,
-            items,
-            npcs,
-            coins_collected: 0,
-            keys_collected: 0,
-            monochrome_mode: false,  // 默认彩色模式
+            stats: PlayerStats::default(),
+            items: Vec::new(),
+            npcs: Vec::new(),
+            monochrome_mode: color_mode == ColorMode::Off,  // 终端不支持颜色时直接用纯色模式
             energy_bar_rect: None,
             pressed_button: None,
-            button_press_time: None,
+            button_held_time: 0.0,
             fullscreen_mode: false,
             minimap_rect: None,
+            peaceful: false,
+            radar_time_left: 0.0,
+            pickup_radius: PICKUP_RADIUS,
+            magnet_time_left: 0.0,
+            difficulty,
+            auto_path: Vec::new(),
+            auto_walk_no_path: false,
+            visited: Vec::new(),
+            wall_near: args.wall_near,
+            wall_far: args.wall_far.max(args.wall_near),
+            show_npcs_on_minimap: true,
+            show_rear_view: false,
+            show_minimap_legend: false,
+            npc_safe_radius: args.npc_safe_radius.max(0.0),
+            explore_goal: (args.explore_goal / 100.0).clamp(0.0, 1.0),
+            explore_bonus_given: false,
+            stamina: MAX_STAMINA,
+            sprint_time_left: 0.0,
+            rng: match args.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            events: Vec::new(),
+            recording: None,
+            demo_after: args.demo_after.max(0.0),
+            last_input: Instant::now(),
+            demo_saved_camera: None,
+            demo_path: Vec::new(),
+        };
+        app.reset_visited();
+        app.spawn_items();
+        app.spawn_npcs();
+        app
+    }
+
+    /// 可以放置物品和NPC的空格子（离边界至少5格）。只取不开门就能从起点走到的格子，钥匙和出口不会被关在门后
+    fn spawn_candidates(&self) -> Vec<(usize, usize)> {
+        let (width, height) = (self.world.width, self.world.height);
+        let start = self.world.get_start_position();
+        let reachable = self.world.reachable_from((start.0 as usize, start.1 as usize));
+        self.world
+            .open_cells()
+            .filter(|&(x, y)| reachable[x][y] && x >= 5 && y >= 5 && x < width - 5 && y < height - 5)
+            .collect()
+    }
+
+    fn spawn_items(&mut self) {
+        let candidates = self.spawn_candidates();
+        let rng = &mut self.rng;
+
+        let mut types = vec![ItemType::Coin; self.difficulty.coin_count()];
+        types.extend(vec![ItemType::Key; self.difficulty.key_count()]);
+        types.push(ItemType::Exit);
+        // 每个迷宫最多出现一个雷达，概率取决于难度
+        if rng.gen_bool(self.difficulty.radar_chance()) {
+            types.push(ItemType::Radar);
+        }
+        if rng.gen_bool(self.difficulty.magnet_chance()) {
+            types.push(ItemType::Magnet);
+        }
+
+        for item_type in types {
+            if let Some(&(x, y)) = candidates.choose(rng) {
+                self.items.push(Item::new(x as f64 + 0.5, y as f64 + 0.5, item_type));
+            }
+        }
+    }
+
+    /// 把每种NPC补足到难度要求的数量，新NPC出生在远离起点和玩家的空格上
+    fn spawn_npcs(&mut self) {
+        // 和平模式下不生成任何NPC
+        if self.peaceful {
+            return;
+        }
+
+        let pos = self.camera.position;
+        let start = self.world.get_start_position();
+        let min_dist_sq = self.npc_safe_radius * self.npc_safe_radius;
+        let far_from = |(px, py): (f64, f64), (x, y): (usize, usize)| {
+            let (dx, dy) = (x as f64 + 0.5 - px, y as f64 + 0.5 - py);
+            dx * dx + dy * dy >= min_dist_sq
+        };
+        let candidates: Vec<(usize, usize)> = self
+            .spawn_candidates()
+            .into_iter()
+            .filter(|&cell| far_from(start, cell) && far_from((pos.x, pos.y), cell))
+            .collect();
+        let rng = &mut self.rng;
+
+        let targets = self.difficulty.npc_types();
+        for &npc_type in targets {
+            let wanted = targets.iter().filter(|&&t| t == npc_type).count();
+            let existing = self.npcs.iter().filter(|npc| npc.npc_type == npc_type).count();
+            if existing >= wanted || self.npcs.len() >= MAX_NPCS {
+                continue;
+            }
+            if let Some(&(x, y)) = candidates.choose(rng) {
+                self.npcs.push(NPC::with_rng(x as f64 + 0.5, y as f64 + 0.5, npc_type, rng));
+            }
         }
     }
 
+    /// 移除跑出地图或被关在墙里的NPC（例如开关合上了它所在的墙），并补充新的
+    fn maintain_npc_population(&mut self) {
+        let world = &self.world;
+        let before = self.npcs.len();
+        self.npcs.retain(|npc| !world.is_wall(npc.x.floor() as i32, npc.y.floor() as i32));
+        if self.npcs.len() < before {
+            self.spawn_npcs();
+        }
+    }
+
+    fn toggle_peaceful(&mut self) {
+        self.peaceful = !self.peaceful;
+        self.npcs.clear();
+        self.spawn_npcs();
+    }
+
     fn regenerate_maze(&mut self) {
         let current_monochrome = self.monochrome_mode;  // 保存当前模式设置
         
-        self.world = World::new_random();
+        self.world = World::from_rng(&mut self.rng);
         let start_pos = self.world.get_start_position();
-        self.camera.position = Vec2::new(start_pos.0, start_pos.1);
-        self.steps = 0;
-        self.coins_collected = 0;
-        self.keys_collected = 0;
+        self.camera.position = start_pos.into();
+        self.stats.start_maze(false);
         
         self.items.clear();
         self.npcs.clear();
+        self.auto_path.clear();
+        self.auto_walk_no_path = false;
+        self.reset_visited();
+        self.explore_bonus_given = false;
         
         self.monochrome_mode = current_monochrome;  // 恢复模式设置
         self.energy_bar_rect = None;  // 重置energy条矩形
         
-        for _ in 0..8 {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..self.world.width - 5) as f64;
-                let y = rng.gen_range(5..self.world.height - 5) as f64;
-                if !self.world.is_wall(x as i32, y as i32) {
-                    self.items.push(Item::new(x + 0.5, y + 0.5, ItemType::Coin));
-                    break;
-                }
-            }
+        self.spawn_items();
+        self.spawn_npcs();
+    }
+
+    // 无尽模式：换一张新迷宫，但保留金币、钥匙和生命值
+    fn regenerate_maze_keep_progress(&mut self) {
+        let stats = self.stats;
+        self.regenerate_maze();
+        self.stats = stats;
+        self.stats.start_maze(true);
+    }
+
+    /// 执行一个会改变游戏状态的操作，开启录像时记录下来
+    fn apply_action(&mut self, action: Action) {
+        if let Some(recording) = &mut self.recording {
+            recording.entries.push(ReplayEntry::Action(action));
         }
-        
-        for _ in 0..2 {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..self.world.width - 5) as f64;
-                let y = rng.gen_range(5..self.world.height - 5) as f64;
-                if !self.world.is_wall(x as i32, y as i32) {
-                    self.items.push(Item::new(x + 0.5, y + 0.5, ItemType::Key));
-                    break;
+        match action {
+            Action::Button(button) => self.execute_button_action(button),
+            Action::Sprint(button) => self.sprint(button),
+            Action::Rotate(angle) => self.camera.rotate_absolute(angle),
+            Action::LookUp(amount) => self.camera.look_up(amount),
+            Action::LookDown(amount) => self.camera.look_down(amount),
+            Action::Jump => {
+                if self.camera.z_position == 0.0 {
+                    self.camera.z_velocity = 0.3;
+                    self.events.push(GameEvent::Jump);
                 }
             }
+            Action::Interact => self.interact(),
+            Action::NewMazeKeepProgress => self.regenerate_maze_keep_progress(),
+            Action::TogglePeaceful => self.toggle_peaceful(),
+            Action::ToggleAutoWalk => self.toggle_auto_walk(),
+            Action::CancelAutoWalk => self.auto_path.clear(),
+            Action::ToggleNoclip => self.camera.noclip = !self.camera.noclip,
         }
+    }
 
-        for npc_type in [NPCType::Wanderer, NPCType::Guard] {
-            let mut rng = rand::thread_rng();
-            loop {
-                let x = rng.gen_range(5..self.world.width - 5) as f64;
-                let y = rng.gen_range(5..self.world.height - 5) as f64;
-                if !self.world.is_wall(x as i32, y as i32) {
-                    self.npcs.push(NPC::new(x + 0.5, y + 0.5, npc_type));
-                    break;
-                }
-            }
+    /// 取走本帧累积的事件
+    fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn take_step(&mut self) {
+        self.auto_walk_no_path = false;
+        self.stats.step();
+        self.events.push(GameEvent::Footstep);
+    }
+
+    fn is_sprinting(&self) -> bool {
+        self.sprint_time_left > 0.0 && self.stamina > 0.0
+    }
+
+    /// Shift+WASD：耐力还有剩余时以更快的速度移动
+    fn sprint(&mut self, button: Button) {
+        self.sprint_time_left = SPRINT_HOLD;
+        self.execute_button_action(button);
+    }
+
+    /// 冲刺时消耗耐力，否则慢慢恢复
+    fn update_stamina(&mut self, delta_time: f64) {
+        if self.is_sprinting() {
+            self.stamina = (self.stamina - SPRINT_DRAIN * delta_time).max(0.0);
+        } else {
+            self.stamina = (self.stamina + STAMINA_REGEN * delta_time).min(MAX_STAMINA);
         }
+        self.sprint_time_left = (self.sprint_time_left - delta_time).max(0.0);
     }
 
     fn execute_button_action(&mut self, button: Button) {
+        let step = if self.is_sprinting() { BUTTON_STEP * SPRINT_MULTIPLIER } else { BUTTON_STEP };
         match button {
             Button::Forward => {
-                self.camera.move_forward(&self.world, 1.5);
-                self.steps += 1;
+                self.camera.move_forward(&self.world, step);
+                self.take_step();
                 self.check_item_collection();
             }
             Button::Backward => {
-                self.camera.move_backward(&self.world, 1.5);
-                self.steps += 1;
+                self.camera.move_backward(&self.world, step);
+                self.take_step();
                 self.check_item_collection();
             }
             Button::StrafeLeft => {
-                self.camera.strafe_left(&self.world, 1.5);
-                self.steps += 1;
+                self.camera.strafe_left(&self.world, step);
+                self.take_step();
                 self.check_item_collection();
             }
             Button::StrafeRight => {
-                self.camera.strafe_right(&self.world, 1.5);
-                self.steps += 1;
+                self.camera.strafe_right(&self.world, step);
+                self.take_step();
                 self.check_item_collection();
             }
-            Button::RotateLeft => self.camera.rotate(-1.5),
-            Button::RotateRight => self.camera.rotate(1.5),
+            Button::RotateLeft => self.camera.rotate(-BUTTON_STEP),
+            Button::RotateRight => self.camera.rotate(BUTTON_STEP),
             Button::ResetView => {
                 self.camera.pitch = 0.0;
                 self.camera.z_position = 0.0;
@@ -306,48 +628,218 @@ impl App {
         }
     }
 
+    fn interact(&mut self) {
+        let (x, y) = self.camera.facing_cell();
+        self.world.interact(x, y, &mut self.stats.keys);
+    }
+
+    fn reset_visited(&mut self) {
+        self.visited = vec![vec![false; self.world.height]; self.world.width];
+    }
+
+    /// 沿视线把能看到的空格标记为已探索
+    fn reveal_sight_lines(&mut self) {
+        for (x, y) in self.camera.visible_cells(&self.world, SIGHT_RAYS) {
+            self.visited[x][y] = true;
+        }
+    }
+
+    fn explore_goal_reached(&self) -> bool {
+        self.explore_goal <= 0.0 || self.world.explored_fraction(&self.visited) >= self.explore_goal
+    }
+
+    /// 钥匙没找齐，或设置了探索目标且还没达到时，出口不能进入
+    fn exit_locked(&self) -> bool {
+        (self.stats.keys_found as usize) < self.difficulty.key_count() || !self.explore_goal_reached()
+    }
+
+    /// 第一次达到探索目标时奖励金币
+    fn check_exploration_bonus(&mut self) {
+        if self.explore_goal > 0.0 && !self.explore_bonus_given && self.explore_goal_reached() {
+            self.stats.collect_coins(EXPLORE_BONUS_COINS);
+            self.explore_bonus_given = true;
+        }
+    }
+
+    /// 开启或关闭自动寻路：从当前所在格走到出口。路线不经过门，走不到时在状态栏提示
+    fn toggle_auto_walk(&mut self) {
+        if !self.auto_path.is_empty() {
+            self.auto_path.clear();
+            return;
+        }
+
+        let from = (self.camera.position.x as usize, self.camera.position.y as usize);
+        let path = self
+            .items
+            .iter()
+            .find(|item| item.item_type == ItemType::Exit && !item.collected)
+            .and_then(|exit| self.world.find_path(from, (exit.x as usize, exit.y as usize)));
+        self.auto_walk_no_path = path.is_none();
+        self.auto_path = path.unwrap_or_default();
+    }
+
+    fn update_auto_walk(&mut self, delta_time: f64) {
+        if step_along_path(&mut self.camera, &self.world, &mut self.auto_path, delta_time) {
+            self.take_step();
+            self.check_item_collection();
+        }
+    }
+
+    fn is_demo(&self) -> bool {
+        self.demo_saved_camera.is_some()
+    }
+
+    /// 无操作时间超过阈值时进入演示模式；录像时不进入，否则镜头移动会混进录像
+    fn update_idle(&mut self) {
+        if self.demo_after > 0.0
+            && !self.is_demo()
+            && self.recording.is_none()
+            && self.last_input.elapsed().as_secs_f64() >= self.demo_after
+        {
+            self.demo_saved_camera = Some(self.camera.clone());
+            self.demo_path.clear();
+        }
+    }
+
+    /// 记录一次玩家输入。演示模式下的第一个输入只用来交还控制权，此时返回 true，调用方不再处理这个输入
+    fn note_input(&mut self) -> bool {
+        self.last_input = Instant::now();
+        if self.is_demo() {
+            self.exit_demo();
+            return true;
+        }
+        false
+    }
+
+    fn exit_demo(&mut self) {
+        if let Some(camera) = self.demo_saved_camera.take() {
+            self.camera = camera;
+        }
+        self.demo_path.clear();
+    }
+
+    /// 演示模式的一帧：镜头沿路线走向随机挑选的空格，到达后再挑下一个。
+    /// 游戏本身暂停，不拾取物品也不计步
+    fn demo_tick(&mut self, delta_time: f64) {
+        self.animation_frame = (self.animation_frame + 1) % 60;
+        if self.demo_path.is_empty() {
+            let from = (self.camera.position.x as usize, self.camera.position.y as usize);
+            let cells: Vec<(usize, usize)> = self.world.open_cells().collect();
+            // 演示路线用独立的随机数，不打乱指定种子时的布局序列
+            if let Some(&to) = cells.choose(&mut rand::thread_rng()) {
+                self.demo_path = self.world.find_path(from, to).unwrap_or_default();
+            }
+        }
+        if self.demo_path.is_empty() {
+            // 找不到路线时原地慢慢转圈
+            self.camera.rotate_absolute(0.5 * delta_time);
+            return;
+        }
+        step_along_path(&mut self.camera, &self.world, &mut self.demo_path, delta_time);
+    }
+
+    fn update_magnet(&mut self, delta_time: f64) {
+        if self.magnet_time_left <= 0.0 {
+            return;
+        }
+        self.magnet_time_left = (self.magnet_time_left - delta_time).max(0.0);
+        if self.magnet_time_left == 0.0 {
+            self.pickup_radius = PICKUP_RADIUS;
+        }
+    }
+
     fn check_item_collection(&mut self) {
         let pos = self.camera.position;
+        if self.world.tile(pos.x.floor() as i32, pos.y.floor() as i32).is_trap {
+            self.stats.take_damage(TRAP_DAMAGE);
+            self.events.push(GameEvent::Damage(TRAP_DAMAGE));
+        }
+        let exit_locked = self.exit_locked();
         for item in &mut self.items {
-            if !item.collected && item.distance_to(pos.x, pos.y) < 0.6 {
+            if item.item_type == ItemType::Exit && exit_locked {
+                continue;
+            }
+            if !item.collected && item.distance_to(pos.x, pos.y) < self.pickup_radius {
                 item.collected = true;
                 match item.item_type {
-                    ItemType::Coin => self.coins_collected += 1,
-                    ItemType::Key => self.keys_collected += 1,
-                    ItemType::Health => self.health = (self.health + 20.0).min(100.0),
+                    ItemType::Coin => {
+                        self.stats.collect_coin();
+                        self.events.push(GameEvent::CoinPickup);
+                    }
+                    ItemType::Key => {
+                        self.stats.collect_key();
+                        self.events.push(GameEvent::KeyPickup);
+                    }
+                    ItemType::Health => self.stats.heal(20.0),
+                    ItemType::Radar => self.radar_time_left = RADAR_DURATION,
+                    ItemType::Magnet => {
+                        self.magnet_time_left = MAGNET_DURATION;
+                        self.pickup_radius = MAGNET_PICKUP_RADIUS;
+                    }
                     _ => {}
                 }
             }
         }
     }
     
-    fn update_npcs(&mut self) {
+    fn update_npcs(&mut self, delta_time: f64) {
+        if self.peaceful {
+            return;
+        }
         let map = self.world.get_map();
+        let pos = self.camera.position;
         for npc in &mut self.npcs {
-            npc.update(map, 1.0 / 30.0);
+            npc.update_with_rng(map, delta_time, &mut self.rng);
+            npc.update_vision(pos.x, pos.y, &self.world);
         }
+        self.maintain_npc_population();
     }
 
     fn handle_events(&mut self) -> io::Result<()> {
         if event::poll(Duration::from_millis(16))? {
-            match event::read()? {
+            let event = event::read()?;
+            if matches!(event, Event::Key(_) | Event::Mouse(_)) && self.note_input() {
+                return Ok(());
+            }
+            match event {
                 Event::Key(key) => {
+                    // 任何手动操作都会打断自动寻路
+                    if key.code != KeyCode::Char('h') && !self.auto_path.is_empty() {
+                        self.apply_action(Action::CancelAutoWalk);
+                    }
                     match key.code {
-                        KeyCode::Char('w') | KeyCode::Up => self.execute_button_action(Button::Forward),
-                        KeyCode::Char('s') | KeyCode::Down => self.execute_button_action(Button::Backward),
-                        KeyCode::Char('a') => self.execute_button_action(Button::StrafeLeft),
-                        KeyCode::Char('d') => self.execute_button_action(Button::StrafeRight),
-                        KeyCode::Left => self.execute_button_action(Button::RotateLeft),
-                        KeyCode::Right => self.execute_button_action(Button::RotateRight),
-                        KeyCode::Char('e') => self.camera.look_up(1.0),
-                        KeyCode::Char('c') => self.camera.look_down(1.0),
-                        KeyCode::Char(' ') => {
-                            if self.camera.z_position == 0.0 {
-                                self.camera.z_velocity = 0.3;
-                            }
-                        }
-                        KeyCode::Char('r') => self.execute_button_action(Button::NewMaze),
+                        KeyCode::Char('w') | KeyCode::Up => self.apply_action(Action::Button(Button::Forward)),
+                        KeyCode::Char('s') | KeyCode::Down => self.apply_action(Action::Button(Button::Backward)),
+                        KeyCode::Char('a') => self.apply_action(Action::Button(Button::StrafeLeft)),
+                        KeyCode::Char('d') => self.apply_action(Action::Button(Button::StrafeRight)),
+                        KeyCode::Char('W') => self.apply_action(Action::Sprint(Button::Forward)),
+                        KeyCode::Char('S') => self.apply_action(Action::Sprint(Button::Backward)),
+                        KeyCode::Char('A') => self.apply_action(Action::Sprint(Button::StrafeLeft)),
+                        KeyCode::Char('D') => self.apply_action(Action::Sprint(Button::StrafeRight)),
+                        KeyCode::Left => self.apply_action(Action::Button(Button::RotateLeft)),
+                        KeyCode::Right => self.apply_action(Action::Button(Button::RotateRight)),
+                        KeyCode::Char('e') => self.apply_action(Action::LookUp(1.0)),
+                        KeyCode::Char('c') => self.apply_action(Action::LookDown(1.0)),
+                        KeyCode::Char(' ') => self.apply_action(Action::Jump),
+                        KeyCode::Char('f') | KeyCode::Enter => self.apply_action(Action::Interact),
+                        KeyCode::Char('r') => self.apply_action(Action::Button(Button::NewMaze)),
+                        KeyCode::Char('x') => self.apply_action(Action::NewMazeKeepProgress),
+                        KeyCode::Char('n') => self.apply_action(Action::TogglePeaceful),
+                        KeyCode::Char('h') => self.apply_action(Action::ToggleAutoWalk),
+                        KeyCode::Char('b') => self.show_rear_view = !self.show_rear_view,
+                        KeyCode::Char('l') => self.show_minimap_legend = !self.show_minimap_legend,
+                        KeyCode::Char('g') => self.apply_action(Action::ToggleNoclip),
+                        KeyCode::Char('v') => self.show_npcs_on_minimap = !self.show_npcs_on_minimap,
+                        KeyCode::Char('t') => self.renderer.wall_texture = self.renderer.wall_texture.next(),
+                        KeyCode::Char('k') => self.renderer.ceiling_style = self.renderer.ceiling_style.next(),
+                        KeyCode::Char('p') => self.renderer.color_blind = !self.renderer.color_blind,
                         KeyCode::Char('m') => self.monochrome_mode = !self.monochrome_mode, // 切换纯色模式
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            self.target_fps = (self.target_fps + FPS_STEP).min(MAX_FPS);
+                        }
+                        KeyCode::Char('-') => {
+                            self.target_fps = self.target_fps.saturating_sub(FPS_STEP).max(MIN_FPS);
+                        }
                         KeyCode::Char('q') | KeyCode::Esc => self.running = false,
                         _ => {}
                     }
@@ -355,6 +847,9 @@ impl App {
                 Event::Mouse(mouse) => {
                     match mouse.kind {
                         MouseEventKind::Down(MouseButton::Left) => {
+                            if !self.auto_path.is_empty() {
+                                self.apply_action(Action::CancelAutoWalk);
+                            }
                             let mut clicked_button = None;
                             for button in &mut self.buttons {
                                 if button.is_inside(mouse.column, mouse.row) {
@@ -381,10 +876,10 @@ impl App {
                             }
                             
                             if let Some(btn) = clicked_button {
-                                self.execute_button_action(btn);
+                                self.apply_action(Action::Button(btn));
                                 // 记录按压的按钮和时间，用于持续移动
                                 self.pressed_button = Some(btn);
-                                self.button_press_time = Some(Instant::now());
+                                self.button_held_time = 0.0;
                             }
                             self.mouse_dragging = true;
                             self.last_mouse_pos = Some((mouse.column, mouse.row));
@@ -396,29 +891,27 @@ impl App {
                             self.mouse_dragging = false;
                             // 清除按压状态
                             self.pressed_button = None;
-                            self.button_press_time = None;
+                            self.button_held_time = 0.0;
                         }
-                        MouseEventKind::Drag(MouseButton::Left) => {
-                            if self.mouse_dragging {
-                                if let Some((last_x, last_y)) = self.last_mouse_pos {
-                                    let delta_x = mouse.column as i16 - last_x as i16;
-                                    let delta_y = mouse.row as i16 - last_y as i16;
-                                    
-                                    if delta_x.abs() > 0 {
-                                        let rotation = delta_x as f64 * 0.02;
-                                        self.camera.rotate_absolute(rotation);
-                                    }
-                                    
-                                    if delta_y.abs() > 0 {
-                                        if delta_y < 0 {
-                                            self.camera.look_up(delta_y.abs() as f64 * 0.5);
-                                        } else {
-                                            self.camera.look_down(delta_y as f64 * 0.5);
-                                        }
+                        MouseEventKind::Drag(MouseButton::Left) if self.mouse_dragging => {
+                            if let Some((last_x, last_y)) = self.last_mouse_pos {
+                                let delta_x = mouse.column as i16 - last_x as i16;
+                                let delta_y = mouse.row as i16 - last_y as i16;
+                                
+                                if delta_x.abs() > 0 {
+                                    let rotation = delta_x as f64 * 0.02;
+                                    self.apply_action(Action::Rotate(rotation));
+                                }
+                                
+                                if delta_y.abs() > 0 {
+                                    if delta_y < 0 {
+                                        self.apply_action(Action::LookUp(delta_y.abs() as f64 * 0.5));
+                                    } else {
+                                        self.apply_action(Action::LookDown(delta_y as f64 * 0.5));
                                     }
                                 }
-                                self.last_mouse_pos = Some((mouse.column, mouse.row));
                             }
+                            self.last_mouse_pos = Some((mouse.column, mouse.row));
                         }
                         MouseEventKind::Moved => {
                             for button in &mut self.buttons {
@@ -434,269 +927,370 @@ impl App {
         Ok(())
     }
 
-    fn render(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+    /// 推进一帧游戏状态（物理、NPC、自动寻路、按钮连发），不涉及终端绘制
+    fn tick(&mut self, delta_time: f64) {
+        if let Some(recording) = &mut self.recording {
+            recording.entries.push(ReplayEntry::Tick(delta_time));
+        }
         self.animation_frame = (self.animation_frame + 1) % 60;
-        self.camera.update(1.0 / 30.0);
-        self.update_npcs();
+        self.stats.advance(delta_time);
+        self.camera.update(delta_time);
+        self.update_npcs(delta_time);
+        self.update_auto_walk(delta_time);
+        self.update_stamina(delta_time);
+        self.reveal_sight_lines();
+        self.check_exploration_bonus();
+        self.radar_time_left = (self.radar_time_left - delta_time).max(0.0);
+        self.update_magnet(delta_time);
         
-        // 处理持续按钮按压
-        if let Some(button) = self.pressed_button {
-            if let Some(press_time) = self.button_press_time {
-                let elapsed = Instant::now().duration_since(press_time);
-                // 按下超过300毫秒后开始持续移动，每100毫秒执行一次
-                if elapsed.as_millis() > 300 && (elapsed.as_millis() - 300) % 100 < 16 {
-                    match button {
-                        Button::Forward | Button::Backward | Button::StrafeLeft | Button::StrafeRight => {
-                            // 只对移动按钮执行持续移动
-                            self.execute_button_action(button);
-                        }
-                        _ => {} // 其他按钮不执行持续动作
-                    }
-                }
-            }
+        self.repeat_held_button(delta_time);
+    }
+
+    /// 按住移动按钮时按经过的时间连发，一帧时间较长时可能连发多次，和帧率无关
+    fn repeat_held_button(&mut self, delta_time: f64) {
+        // 只对移动按钮执行持续移动
+        let Some(button @ (Button::Forward | Button::Backward | Button::StrafeLeft | Button::StrafeRight)) =
+            self.pressed_button
+        else {
+            return;
+        };
+        self.button_held_time += delta_time;
+        while self.button_held_time >= BUTTON_REPEAT_DELAY {
+            self.apply_action(Action::Button(button));
+            self.button_held_time -= BUTTON_REPEAT_INTERVAL;
         }
+    }
+
+    fn render(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+        terminal.draw(|frame| self.draw(frame))?;
+        Ok(())
+    }
+
+    /// 把整个界面画到 frame 上，和具体终端无关，测试里可以用 TestBackend
+    fn draw(&mut self, frame: &mut Frame) {
+        let size = frame.area();
         
-        terminal.draw(|frame| {
-            let size = frame.area();
-            
-            // 根据全屏模式调整布局
-            if self.fullscreen_mode {
-                // 全屏模式：3D视角占据整个屏幕
-                self.renderer.render(frame, size, &self.camera, &self.world, &self.items, &self.npcs, self.monochrome_mode);
-            } else {
-                // 正常模式：三栏布局
-                let main_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([
-                        Constraint::Percentage(20),
-                        Constraint::Percentage(60),
-                        Constraint::Percentage(20),
-                    ])
-                    .split(size);
-
-                let left_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Length(3),
-                        Constraint::Length(3),
-                        Constraint::Length(3),
-                        Constraint::Length(3),
-                        Constraint::Min(5),
-                        Constraint::Length(8),
-                    ])
-                    .split(main_chunks[0]);
-
-                let center_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Min(10),
-                        Constraint::Length(5),
-                    ])
-                    .split(main_chunks[1]);
-
-                let right_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Percentage(60),
-                        Constraint::Percentage(40),
-                    ])
-                    .split(main_chunks[2]);
-
-                self.renderer.render(frame, center_chunks[0], &self.camera, &self.world, &self.items, &self.npcs, self.monochrome_mode);
-
-                self.buttons[0].rect = left_chunks[0];
-                self.buttons[1].rect = left_chunks[1];
-                self.buttons[2].rect = left_chunks[2];
-                self.buttons[3].rect = left_chunks[3];
-
-                for i in 0..4 {
-                    let button = &self.buttons[i];
-                    let label = button.get_label();
-                    let style = button.get_style();
-                    
-                    let border_type = if button.pressed {
-                        BorderType::Double
-                    } else if button.hover {
-                        BorderType::Thick
-                    } else {
-                        BorderType::Rounded
-                    };
-
-                    let btn = Paragraph::new(label)
-                        .style(style)
-                        .alignment(Alignment::Center)
-                        .block(Block::default()
-                            .borders(Borders::ALL)
-                            .border_type(border_type));
-                    frame.render_widget(btn, left_chunks[i]);
-                }
+        // 根据全屏模式调整布局
+        if self.fullscreen_mode {
+            // 全屏模式：3D视角占据整个屏幕
+            let ctx = RenderContext {
+                camera: &self.camera,
+                world: &self.world,
+                items: &self.items,
+                npcs: &self.npcs,
+                monochrome_mode: self.monochrome_mode,
+            };
+            self.renderer.render(frame, size, &ctx);
+            if self.show_rear_view {
+                self.renderer.render_rear_view(frame, size, &ctx);
+            }
+        } else {
+            // 正常模式：三栏布局
+            let main_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(20),
+                ])
+                .split(size);
 
-                self.buttons[4].rect = Rect {
-                    x: left_chunks[5].x + 1,
-                    y: left_chunks[5].y + 1,
-                    width: left_chunks[5].width / 2 - 1,
-                    height: 3,
-                };
-                self.buttons[5].rect = Rect {
-                    x: left_chunks[5].x + left_chunks[5].width / 2,
-                    y: left_chunks[5].y + 1,
-                    width: left_chunks[5].width / 2 - 1,
-                    height: 3,
-                };
-                self.buttons[6].rect = Rect {
-                    x: left_chunks[5].x + 1,
-                    y: left_chunks[5].y + 4,
-                    width: left_chunks[5].width / 2 - 1,
-                    height: 3,
-                };
-                self.buttons[7].rect = Rect {
-                    x: left_chunks[5].x + left_chunks[5].width / 2,
-                    y: left_chunks[5].y + 4,
-                    width: left_chunks[5].width / 2 - 1,
-                    height: 3,
-                };
+            let left_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                    Constraint::Min(5),
+                    Constraint::Length(8),
+                ])
+                .split(main_chunks[0]);
 
-                for i in 4..8 {
-                    let button = &self.buttons[i];
-                    let label = button.get_label();
-                    let style = button.get_style();
-                    
-                    let border_type = if button.pressed {
-                        BorderType::Double
-                    } else if button.hover {
-                        BorderType::Thick
-                    } else {
-                        BorderType::Rounded
-                    };
-
-                    let btn = Paragraph::new(label)
-                        .style(style)
-                        .alignment(Alignment::Center)
-                        .block(Block::default()
-                            .borders(Borders::ALL)
-                            .border_type(border_type));
-                    frame.render_widget(btn, button.rect);
-                }
+            let center_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(10),
+                    Constraint::Length(5),
+                ])
+                .split(main_chunks[1]);
 
-                let controls_block = Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded)
-                    .title("🎮 Controls");
-                frame.render_widget(controls_block, left_chunks[5]);
+            let right_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(40),
+                ])
+                .split(main_chunks[2]);
 
-                let pos = self.camera.position;
-                let dir = self.camera.direction;
+            let ctx = RenderContext {
+                camera: &self.camera,
+                world: &self.world,
+                items: &self.items,
+                npcs: &self.npcs,
+                monochrome_mode: self.monochrome_mode,
+            };
+            self.renderer.render(frame, center_chunks[0], &ctx);
+            if self.show_rear_view {
+                self.renderer.render_rear_view(frame, center_chunks[0], &ctx);
+            }
+
+            self.buttons[0].rect = left_chunks[0];
+            self.buttons[1].rect = left_chunks[1];
+            self.buttons[2].rect = left_chunks[2];
+            self.buttons[3].rect = left_chunks[3];
+
+            for i in 0..4 {
+                let button = &self.buttons[i];
+                let label = self.renderer.display_text(button.get_label());
+                let style = button.get_style();
                 
-                let wall_dist = self.get_nearest_wall_distance();
-                let proximity_warning = if wall_dist < 1.5 {
-                    Span::styled("⚠ WALL! ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK))
-                } else if wall_dist < 3.0 {
-                    Span::styled("⚠ Close ", Style::default().fg(Color::Yellow))
+                let border_type = if button.pressed {
+                    BorderType::Double
+                } else if button.hover {
+                    BorderType::Thick
                 } else {
-                    Span::styled("✓ Clear ", Style::default().fg(Color::Green))
+                    BorderType::Rounded
                 };
 
-                let animation_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-                let anim_char = animation_chars[self.animation_frame / 6 % animation_chars.len()];
+                let btn = Paragraph::new(label)
+                    .style(style)
+                    .alignment(Alignment::Center)
+                    .block(Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(border_type));
+                frame.render_widget(btn, left_chunks[i]);
+            }
 
-                let pitch_degrees = (self.camera.pitch * 180.0 / std::f64::consts::PI) as i32;
-                let pitch_indicator = if self.camera.z_position > 0.1 {
-                    Span::styled("↑ JUMP ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
-                } else if pitch_degrees > 10 {
-                    Span::styled(format!("↗ +{}° ", pitch_degrees), Style::default().fg(Color::Cyan))
-                } else if pitch_degrees < -10 {
-                    Span::styled(format!("↘ {}° ", pitch_degrees), Style::default().fg(Color::Blue))
+            self.buttons[4].rect = Rect {
+                x: left_chunks[5].x + 1,
+                y: left_chunks[5].y + 1,
+                width: left_chunks[5].width / 2 - 1,
+                height: 3,
+            };
+            self.buttons[5].rect = Rect {
+                x: left_chunks[5].x + left_chunks[5].width / 2,
+                y: left_chunks[5].y + 1,
+                width: left_chunks[5].width / 2 - 1,
+                height: 3,
+            };
+            self.buttons[6].rect = Rect {
+                x: left_chunks[5].x + 1,
+                y: left_chunks[5].y + 4,
+                width: left_chunks[5].width / 2 - 1,
+                height: 3,
+            };
+            self.buttons[7].rect = Rect {
+                x: left_chunks[5].x + left_chunks[5].width / 2,
+                y: left_chunks[5].y + 4,
+                width: left_chunks[5].width / 2 - 1,
+                height: 3,
+            };
+
+            for i in 4..8 {
+                let button = &self.buttons[i];
+                let label = self.renderer.display_text(button.get_label());
+                let style = button.get_style();
+                
+                let border_type = if button.pressed {
+                    BorderType::Double
+                } else if button.hover {
+                    BorderType::Thick
                 } else {
-                    Span::styled("→ Level ", Style::default().fg(Color::Green))
+                    BorderType::Rounded
                 };
 
-                let info_lines = vec![
-                    Line::from(vec![
-                        Span::styled(format!("{} ", anim_char), Style::default().fg(Color::Cyan)),
-                        Span::styled("Position: ", Style::default().fg(Color::Gray)),
-                        Span::raw(format!("({:.1}, {:.1})", pos.x, pos.y)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Direction: ", Style::default().fg(Color::Gray)),
-                        Span::raw(format!("({:.2}, {:.2})", dir.x, dir.y)),
-                    ]),
-                    Line::from(vec![
-                        pitch_indicator,
-                    ]),
-                    Line::from(vec![
-                        proximity_warning,
-                        Span::raw(format!("Dist: {:.1}", wall_dist)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(format!("Steps: {}", self.steps), Style::default().fg(Color::Magenta)),
-                        Span::raw("  "),
-                        Span::styled(format!("FPS: {:.0}", self.fps), Style::default().fg(Color::Cyan)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Mode: ", Style::default().fg(Color::Gray)),
-                        Span::styled(if self.monochrome_mode { "MONOCHROME" } else { "COLOR" }, 
-                            Style::default().fg(if self.monochrome_mode { Color::White } else { Color::Cyan }).add_modifier(Modifier::BOLD)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("◆", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                        Span::raw(format!(":{} ", self.coins_collected)),
-                        Span::styled("🔑", Style::default().fg(Color::Cyan)),
-                        Span::raw(format!(":{}", self.keys_collected)),
-                    ]),
-                ];
-
-                let info = Paragraph::new(info_lines)
+                let btn = Paragraph::new(label)
+                    .style(style)
+                    .alignment(Alignment::Center)
                     .block(Block::default()
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded)
-                        .title("📊 Status"))
-                    .alignment(Alignment::Left);
-                frame.render_widget(info, center_chunks[1]);
-
-                self.renderer.render_minimap(frame, right_chunks[0], &self.camera, &self.world, &self.items, &self.npcs, self.monochrome_mode);
-
-                let help_text = vec![
-                    Line::from(vec![
-                        Span::styled("🖱️ Mouse", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                    ]),
-                    Line::from("• Click buttons"),
-                    Line::from("• Drag X: Rotate"),
-                    Line::from("• Drag Y: Look"),
-                    Line::from("• Click Map: Fullscreen"),
-                    Line::from(""),
-                    Line::from(vec![
-                        Span::styled("⌨️ Keyboard", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-                    ]),
-                    Line::from("WASD: Move"),
-                    Line::from("←→: Rotate"),
-                    Line::from("E/C: Look up/down"),
-                    Line::from("Space: Jump"),
-                    Line::from("R: New maze"),
-                    Line::from("Q: Quit"),
-                    Line::from("M: Color/Mono"),
-                ];
-
-                let help = Paragraph::new(help_text)
+                        .border_type(border_type));
+                frame.render_widget(btn, button.rect);
+            }
+
+            let controls_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(self.renderer.display_text("🎮 Controls"));
+            frame.render_widget(controls_block, left_chunks[5]);
+
+            let pos = self.camera.position;
+            let dir = self.camera.direction;
+            
+            let wall_dist = self.get_nearest_wall_distance();
+            let explored = self.world.explored_fraction(&self.visited) * 100.0;
+            let explored_text = if self.explore_goal > 0.0 {
+                format!("Explored: {:.0}% / {:.0}%", explored, self.explore_goal * 100.0)
+            } else {
+                format!("Explored: {:.0}%", explored)
+            };
+            let looking_at = match self.world.cast_ray(pos, dir) {
+                Some(hit) => format!("{}, {:.1} away", hit.wall_type.name(), hit.distance),
+                None => "nothing".to_string(),
+            };
+            let proximity_warning = match wall_proximity_level(wall_dist, self.wall_near, self.wall_far) {
+                WallProximity::Danger => Span::styled("⚠ WALL! ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK)),
+                WallProximity::Close => Span::styled("⚠ Close ", Style::default().fg(Color::Yellow)),
+                WallProximity::Clear => Span::styled("✓ Clear ", Style::default().fg(Color::Green)),
+            };
+
+            let animation_chars: &[char] = if self.renderer.ascii_only {
+                &['|', '/', '-', '\\']
+            } else {
+                &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏']
+            };
+            let anim_char = animation_chars[self.animation_frame / 6 % animation_chars.len()];
+
+            let pitch_degrees = (self.camera.pitch * 180.0 / std::f64::consts::PI) as i32;
+            let pitch_indicator = if self.camera.z_position > 0.1 {
+                Span::styled("↑ JUMP ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            } else if pitch_degrees > 10 {
+                Span::styled(format!("↗ +{}° ", pitch_degrees), Style::default().fg(Color::Cyan))
+            } else if pitch_degrees < -10 {
+                Span::styled(format!("↘ {}° ", pitch_degrees), Style::default().fg(Color::Blue))
+            } else {
+                Span::styled("→ Level ", Style::default().fg(Color::Green))
+            };
+
+            let mut info_lines = vec![
+                Line::from(vec![
+                    Span::styled(format!("{} ", anim_char), Style::default().fg(Color::Cyan)),
+                    Span::styled("Position: ", Style::default().fg(Color::Gray)),
+                    Span::raw(format!("({:.1}, {:.1})", pos.x, pos.y)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Direction: ", Style::default().fg(Color::Gray)),
+                    Span::raw(format!("({:.2}, {:.2})", dir.x, dir.y)),
+                ]),
+                // 状态栏只显示得下前三行，钥匙进度和视角放在同一行
+                Line::from(vec![
+                    pitch_indicator,
+                    Span::styled(
+                        format!("Keys: {}/{}", self.stats.keys_found, self.difficulty.key_count()),
+                        Style::default().fg(if self.exit_locked() { Color::Yellow } else { Color::Green }),
+                    ),
+                ]),
+                Line::from(vec![
+                    proximity_warning,
+                    Span::raw(format!("Dist: {:.1}", wall_dist)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Looking at: ", Style::default().fg(Color::Gray)),
+                    Span::raw(looking_at),
+                ]),
+                Line::from(vec![
+                    Span::styled(explored_text, Style::default().fg(if self.explore_goal_reached() { Color::Green } else { Color::Yellow })),
+                ]),
+                Line::from(vec![
+                    Span::styled(format!("Steps: {}", self.stats.steps), Style::default().fg(Color::Magenta)),
+                    Span::raw("  "),
+                    Span::styled(format!("Score: {}", self.stats.score), Style::default().fg(Color::Yellow)),
+                    Span::raw("  "),
+                    Span::styled(format!("FPS: {:.0}/{}", self.fps, self.target_fps), Style::default().fg(Color::Cyan)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Mode: ", Style::default().fg(Color::Gray)),
+                    Span::styled(if self.monochrome_mode { "MONOCHROME" } else { "COLOR" }, 
+                        Style::default().fg(if self.monochrome_mode { Color::White } else { Color::Cyan }).add_modifier(Modifier::BOLD)),
+                ]),
+                Line::from(vec![
+                    Span::styled("◆", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(format!(":{} ", self.stats.coins)),
+                    Span::styled("🔑", Style::default().fg(Color::Cyan)),
+                    Span::raw(format!(":{} ", self.stats.keys)),
+                    if self.radar_time_left > 0.0 {
+                        Span::styled(format!("📡:{:.0}s ", self.radar_time_left), Style::default().fg(Color::Magenta))
+                    } else {
+                        Span::raw("")
+                    },
+                    if self.magnet_time_left > 0.0 {
+                        Span::styled(format!("🧲:{:.0}s", self.magnet_time_left), Style::default().fg(Color::LightBlue))
+                    } else {
+                        Span::raw("")
+                    },
+                ]),
+            ];
+
+            if self.auto_walk_no_path {
+                // 状态栏只显示得下前几行，提示放在最上面
+                info_lines.insert(0, Line::from(Span::styled(
+                    "Auto-walk: no path to the exit",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                )));
+            }
+
+            let info = Paragraph::new(self.renderer.display_lines(info_lines))
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(self.renderer.display_text("📊 Status")))
+                .alignment(Alignment::Left);
+            frame.render_widget(info, center_chunks[1]);
+
+            let radar_radius = if self.radar_time_left > 0.0 { Some(RADAR_RADIUS) } else { None };
+            let minimap_npcs: &[NPC] = if self.show_npcs_on_minimap { &self.npcs } else { &[] };
+            let minimap_ctx = RenderContext { npcs: minimap_npcs, ..ctx };
+            self.renderer.render_minimap(frame, right_chunks[0], &minimap_ctx, &self.visited, radar_radius);
+
+            let help_text = vec![
+                Line::from(vec![
+                    Span::styled("🖱️ Mouse", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                ]),
+                Line::from("• Click buttons"),
+                Line::from("• Drag X: Rotate"),
+                Line::from("• Drag Y: Look"),
+                Line::from("• Click Map: Fullscreen"),
+                Line::from(""),
+                Line::from(vec![
+                    Span::styled("⌨️ Keyboard", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                ]),
+                Line::from("WASD: Move"),
+                Line::from("Shift+WASD: Sprint"),
+                Line::from("←→: Rotate"),
+                Line::from("E/C: Look up/down"),
+                Line::from("Space: Jump"),
+                Line::from("F/Enter: Open/Use"),
+                Line::from("R: New maze"),
+                Line::from("X: New maze, keep items"),
+                Line::from("Q: Quit"),
+                Line::from("M: Color/Mono"),
+                Line::from("N: Peaceful"),
+                Line::from("H: Auto-walk to exit"),
+                Line::from("V: NPCs on minimap"),
+                Line::from("G: Noclip"),
+                Line::from("B: Rear view"),
+                Line::from("T: Wall texture"),
+                Line::from("K: Ceiling style"),
+                Line::from("P: Color-blind palette"),
+                Line::from("L: Minimap legend"),
+                Line::from("+/-: FPS cap"),
+            ];
+
+            if self.show_minimap_legend {
+                self.renderer.render_minimap_legend(frame, right_chunks[1], self.monochrome_mode);
+            } else {
+                let help = Paragraph::new(self.renderer.display_lines(help_text))
                     .block(Block::default()
                         .borders(Borders::ALL)
                         .border_type(BorderType::Rounded)
-                        .title("ℹ️ Help"))
+                        .title(self.renderer.display_text("ℹ️ Help")))
                     .alignment(Alignment::Left);
                 frame.render_widget(help, right_chunks[1]);
-
-                let health_gauge = Gauge::default()
-                    .block(Block::default().borders(Borders::ALL).title("Energy"))
-                    .gauge_style(Style::default().fg(Color::Green).bg(Color::Black))
-                    .percent(self.health as u16);
-                frame.render_widget(health_gauge, left_chunks[4]);
-                
-                // 保存energy条的矩形位置，用于鼠标点击检测
-                self.energy_bar_rect = Some(left_chunks[4]);
-                // 存储地图区域坐标，用于点击检测
-                self.minimap_rect = Some(right_chunks[0]);
             }
-        })?;
-        Ok(())
+
+            let health_gauge = Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("Energy"))
+                .gauge_style(Style::default().fg(if self.is_sprinting() { Color::Yellow } else { Color::Green }).bg(Color::Black))
+                .ratio((self.stamina / MAX_STAMINA).clamp(0.0, 1.0))
+                .label(self.renderer.display_text(&format!("⚡{:.0}  HP {:.0}", self.stamina, self.stats.health)));
+            frame.render_widget(health_gauge, left_chunks[4]);
+            
+            // 保存energy条的矩形位置，用于鼠标点击检测
+            self.energy_bar_rect = Some(left_chunks[4]);
+            // 存储地图区域坐标，用于点击检测
+            self.minimap_rect = Some(right_chunks[0]);
+        }
     }
 
     fn get_nearest_wall_distance(&self) -> f64 {
@@ -714,24 +1308,159 @@ impl App {
     }
 }
 
+/// 每帧沿路径前进一步：先转向下一个格子的中心，朝向对准后再前进，返回本帧是否前进了
+fn step_along_path(camera: &mut Camera, world: &World, path: &mut Vec<(usize, usize)>, delta_time: f64) -> bool {
+    let current = (camera.position.x as usize, camera.position.y as usize);
+    while path.first() == Some(&current) && path.len() > 1 {
+        path.remove(0);
+    }
+    let Some(&(tx, ty)) = path.first() else {
+        return false;
+    };
+
+    let target = Vec2::new(tx as f64 + 0.5, ty as f64 + 0.5);
+    let to_target = target - camera.position;
+    if to_target.magnitude() < 0.1 {
+        // 已经到达最后一格
+        path.clear();
+        return false;
+    }
+
+    // 转向和前进速度按 30 帧每秒标定，再按实际帧时间缩放
+    let frames = delta_time * 30.0;
+    let remaining = camera.face_point(target, 0.2 * frames);
+
+    if remaining.abs() < 0.3 {
+        camera.move_forward(world, frames);
+        return true;
+    }
+    false
+}
+
+/// 不打开终端界面，按录像重放整局，最后打印玩家状态
+fn run_replay(args: &Args, path: &str) -> io::Result<()> {
+    let recording = Recording::load(path)?;
+    let mut args = args.clone();
+    recording.apply_to(&mut args);
+
+    let mut app = App::new(&args);
+    for entry in recording.entries {
+        match entry {
+            ReplayEntry::Tick(delta_time) => app.tick(delta_time),
+            ReplayEntry::Action(action) => app.apply_action(action),
+        }
+    }
+
+    let pos = app.camera.position;
+    println!("Position: ({:.3}, {:.3})", pos.x, pos.y);
+    println!("Steps: {}  Score: {}", app.stats.steps, app.stats.score);
+    println!("Coins: {}  Keys: {}  Health: {:.0}", app.stats.coins, app.stats.keys, app.stats.health);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct EntityDump {
+    kind: String,
+    x: f64,
+    y: f64,
+}
+
+/// `--dump-state` 的输出
+#[derive(Serialize)]
+struct StateDump {
+    seed: u64,
+    difficulty: String,
+    width: usize,
+    height: usize,
+    start: Vec2,
+    /// 每行一个字符串，字符和小地图相同，空格是空地
+    map: Vec<String>,
+    open_cells: usize,
+    items: Vec<EntityDump>,
+    npcs: Vec<EntityDump>,
+}
+
+/// 不打开终端界面，生成一局（不推进任何帧）后打印 JSON 摘要。未指定种子时随机选一个并一起输出，便于复现
+fn run_dump_state(args: &Args) -> io::Result<()> {
+    let mut args = args.clone();
+    let seed = *args.seed.get_or_insert_with(rand::random);
+    let app = App::new(&args);
+
+    let dump = StateDump {
+        seed,
+        difficulty: args
+            .difficulty
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default(),
+        width: app.world.width,
+        height: app.world.height,
+        start: app.world.get_start_position().into(),
+        map: (0..app.world.height)
+            .map(|y| (0..app.world.width).map(|x| app.world.get(x as i32, y as i32).minimap_glyph()).collect())
+            .collect(),
+        open_cells: app.world.open_cells().count(),
+        items: app
+            .items
+            .iter()
+            .map(|item| EntityDump { kind: format!("{:?}", item.item_type), x: item.x, y: item.y })
+            .collect(),
+        npcs: app
+            .npcs
+            .iter()
+            .map(|npc| EntityDump { kind: format!("{:?}", npc.npc_type), x: npc.x, y: npc.y })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&dump).map_err(io::Error::from)?;
+    println!("{}", json);
+    Ok(())
+}
+
 fn main() -> io::Result<()> {
+    let mut args = Args::parse();
+    if let Some(ref path) = args.replay {
+        return run_replay(&args, path);
+    }
+    if args.dump_state {
+        return run_dump_state(&args);
+    }
+    if args.record.is_some() && args.seed.is_none() {
+        args.seed = Some(rand::random());
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
+    let mut app = App::new(&args);
+    if let Some(seed) = args.seed.filter(|_| args.record.is_some()) {
+        app.recording = Some(Recording::new(seed, &args));
+    }
     let mut frame_count = 0;
     let mut fps_timer = Instant::now();
 
     terminal.clear()?;
 
+    let mut last_frame = Instant::now();
+
     while app.running {
         let frame_start = Instant::now();
+        // 用上一帧的实际耗时推进游戏，卡顿时最多按 MAX_DELTA_TIME 计算，避免穿墙
+        let delta_time = last_frame.elapsed().as_secs_f64().min(MAX_DELTA_TIME);
+        last_frame = frame_start;
 
         app.handle_events()?;
+        app.update_idle();
+        if app.is_demo() {
+            app.demo_tick(delta_time);
+        } else {
+            app.tick(delta_time);
+        }
         app.render(&mut terminal)?;
+        // 目前还没有音效等订阅者，本帧事件直接丢弃
+        app.drain_events();
 
         frame_count += 1;
         if fps_timer.elapsed() >= Duration::from_secs(1) {
@@ -740,9 +1469,10 @@ fn main() -> io::Result<()> {
             fps_timer = Instant::now();
         }
 
+        let frame_budget = frame_time(app.target_fps);
         let elapsed = frame_start.elapsed();
-        if elapsed < FRAME_TIME {
-            std::thread::sleep(FRAME_TIME - elapsed);
+        if elapsed < frame_budget {
+            std::thread::sleep(frame_budget - elapsed);
         }
     }
 
@@ -754,5 +1484,430 @@ fn main() -> io::Result<()> {
     )?;
     terminal.show_cursor()?;
 
+    if let (Some(path), Some(recording)) = (&args.record, &app.recording) {
+        recording.save(path)?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_time_follows_the_fps_cap_within_limits() {
+        assert_eq!(frame_time(60), Duration::from_millis(16));
+        assert_eq!(frame_time(20), Duration::from_millis(50));
+        assert_eq!(frame_time(0), frame_time(MIN_FPS));
+        assert_eq!(frame_time(1000), frame_time(MAX_FPS));
+    }
+
+    /// 用命令行参数构造一局固定种子的游戏，不打开终端
+    fn app(extra: &[&str]) -> App {
+        let mut argv = vec!["arsvt3d", "--seed", "7"];
+        argv.extend_from_slice(extra);
+        App::new(&Args::parse_from(argv))
+    }
+
+    /// 在 140x45 的 TestBackend 上画一帧完整界面
+    fn draw(app: &mut App) -> ratatui::buffer::Buffer {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(140, 45)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// 缓冲区里 area 范围内的文字，逐行拼接
+    fn text_in(buffer: &ratatui::buffer::Buffer, area: Rect) -> String {
+        let mut text = String::new();
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                text.push_str(buffer[(x, y)].symbol());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn peaceful_mode_keeps_new_mazes_free_of_npcs() {
+        let mut app = app(&[]);
+        assert!(!app.npcs.is_empty());
+
+        app.apply_action(Action::TogglePeaceful);
+        assert!(app.npcs.is_empty());
+        app.regenerate_maze();
+        assert!(app.npcs.is_empty());
+    }
+
+    #[test]
+    fn seed_and_difficulty_come_from_the_command_line() {
+        let first = app(&[]);
+        let second = app(&[]);
+        assert_eq!(first.world.get_map(), second.world.get_map());
+        assert_eq!(first.world.get_start_position(), second.world.get_start_position());
+
+        let coins = |app: &App| app.items.iter().filter(|item| item.item_type == ItemType::Coin).count();
+        let easy = app(&["--difficulty", "easy"]);
+        let hard = app(&["--difficulty", "hard"]);
+        assert_eq!(easy.difficulty, Difficulty::Easy);
+        assert_eq!(coins(&easy), Difficulty::Easy.coin_count());
+        assert_eq!(coins(&hard), Difficulty::Hard.coin_count());
+    }
+
+    #[test]
+    fn wall_proximity_levels_follow_the_thresholds() {
+        assert_eq!(wall_proximity_level(0.5, 1.5, 3.0), WallProximity::Danger);
+        assert_eq!(wall_proximity_level(1.5, 1.5, 3.0), WallProximity::Close);
+        assert_eq!(wall_proximity_level(2.9, 1.5, 3.0), WallProximity::Close);
+        assert_eq!(wall_proximity_level(3.0, 1.5, 3.0), WallProximity::Clear);
+        // 两个阈值相等时没有 Close 这一档
+        assert_eq!(wall_proximity_level(2.0, 2.0, 2.0), WallProximity::Clear);
+    }
+
+    #[test]
+    fn wall_thresholds_come_from_the_command_line() {
+        let tuned = app(&["--wall-near", "0.8", "--wall-far", "2.5"]);
+        assert_eq!((tuned.wall_near, tuned.wall_far), (0.8, 2.5));
+        // far 小于 near 时抬到 near
+        let swapped = app(&["--wall-near", "2.0", "--wall-far", "1.0"]);
+        assert_eq!(swapped.wall_far, 2.0);
+    }
+
+    #[test]
+    fn npcs_caught_in_walls_are_replaced_up_to_the_target() {
+        let mut app = app(&["--difficulty", "hard"]);
+        let target = Difficulty::Hard.npc_types().len();
+        assert_eq!(app.npcs.len(), target);
+
+        // 把一个NPC关进边界墙里
+        app.npcs[0].x = 0.5;
+        app.npcs[0].y = 0.5;
+        app.maintain_npc_population();
+        assert_eq!(app.npcs.len(), target);
+        let pos = app.camera.position;
+        for npc in &app.npcs {
+            assert!(!app.world.is_wall(npc.x as i32, npc.y as i32));
+            assert!(Vec2::new(npc.x - pos.x, npc.y - pos.y).magnitude() >= app.npc_safe_radius);
+        }
+
+        // 已经满员时不会再多生成
+        app.spawn_npcs();
+        assert_eq!(app.npcs.len(), target);
+    }
+
+    #[test]
+    fn held_button_and_jump_play_out_the_same_at_40_and_120_fps() {
+        // 1.05 秒不落在任何一次连发的时刻上，两种帧率都应该连发 8 次
+        let simulate = |fps: usize| {
+            let mut app = app(&[]);
+            app.peaceful = true;
+            app.npcs.clear();
+            app.items.clear();
+            app.apply_action(Action::Jump);
+            app.apply_action(Action::Button(Button::Forward));
+            app.pressed_button = Some(Button::Forward);
+            let mut heights = Vec::new();
+            for frame in 1..=fps * 21 / 20 {
+                app.tick(1.0 / fps as f64);
+                if frame % (fps / 4) == 0 {
+                    heights.push(app.camera.z_position);
+                }
+            }
+            (app.camera.position, app.stats.steps, heights)
+        };
+
+        let (slow_pos, slow_steps, slow_heights) = simulate(40);
+        let (fast_pos, fast_steps, fast_heights) = simulate(120);
+        assert_eq!(slow_steps, 9);
+        assert_eq!(fast_steps, 9);
+        assert!((slow_pos - fast_pos).magnitude() < 1e-9);
+        // 跳跃按步积分会有误差，只要求每个采样点的高度差在最高点的一成以内
+        let peak = slow_heights.iter().copied().fold(0.0, f64::max);
+        assert!(peak > 1.0);
+        for (slow, fast) in slow_heights.iter().zip(&fast_heights) {
+            assert!((slow - fast).abs() < peak * 0.1, "{} vs {}", slow, fast);
+        }
+    }
+
+    #[test]
+    fn hiding_npcs_removes_them_from_the_minimap() {
+        let mut app = app(&[]);
+        let (cx, cy) = (app.camera.position.x as i64, app.camera.position.y as i64);
+        let (x, y) = app
+            .world
+            .open_cells()
+            .find(|&(x, y)| {
+                let d = (x as i64 - cx).pow(2) + (y as i64 - cy).pow(2);
+                (5..50).contains(&d)
+            })
+            .unwrap();
+        app.npcs = vec![NPC::new(x as f64 + 0.5, y as f64 + 0.5, NPCType::Wanderer)];
+
+        let buffer = draw(&mut app);
+        let minimap = text_in(&buffer, app.minimap_rect.unwrap());
+        assert!(minimap.contains('^'), "NPC missing from minimap:\n{}", minimap);
+
+        app.show_npcs_on_minimap = false;
+        let buffer = draw(&mut app);
+        let minimap = text_in(&buffer, app.minimap_rect.unwrap());
+        assert!(!minimap.contains('^'), "NPC still on minimap:\n{}", minimap);
+    }
+
+    #[test]
+    fn stepping_on_a_trap_drains_health() {
+        let mut app = app(&[]);
+        let (x, y) = app.world.open_cells().next().unwrap();
+        app.items.clear();
+        app.camera.position = Vec2::new(x as f64 + 0.5, y as f64 + 0.5);
+
+        let health = app.stats.health;
+        app.check_item_collection();
+        assert_eq!(app.stats.health, health);
+
+        app.world.set_tile(x as i32, y as i32, arsvt3d::TileInfo { is_trap: true, ..Default::default() });
+        app.check_item_collection();
+        assert_eq!(app.stats.health, health - TRAP_DAMAGE);
+    }
+
+    #[test]
+    fn scripted_walk_collects_a_coin_without_a_terminal() {
+        let mut app = app(&[]);
+        app.npcs.clear();
+        let (x, y) = app
+            .world
+            .open_cells()
+            .find(|&(x, y)| !app.world.is_wall(x as i32 + 1, y as i32))
+            .unwrap();
+        app.camera = Camera::new(Vec2::new(x as f64 + 0.5, y as f64 + 0.5), Vec2::new(1.0, 0.0));
+        app.items = vec![Item::new(x as f64 + 1.5, y as f64 + 0.5, ItemType::Coin)];
+
+        for _ in 0..10 {
+            app.apply_action(Action::Button(Button::Forward));
+            app.tick(1.0 / 30.0);
+        }
+        assert!(app.items[0].collected);
+        assert_eq!(app.stats.coins, 1);
+    }
+
+    #[test]
+    fn npcs_never_spawn_near_the_start() {
+        for seed in 0..30 {
+            let seed = seed.to_string();
+            let app = App::new(&Args::parse_from(["arsvt3d", "--seed", &seed, "--difficulty", "hard", "--npc-safe-radius", "6"]));
+            let (sx, sy) = app.world.get_start_position();
+            assert!(!app.npcs.is_empty());
+            for npc in &app.npcs {
+                let dist = Vec2::new(npc.x - sx, npc.y - sy).magnitude();
+                assert!(dist >= 6.0, "seed {}: NPC {:.1} cells from the start", seed, dist);
+            }
+        }
+    }
+
+    #[test]
+    fn new_maze_keeping_progress_preserves_counts() {
+        let mut app = app(&[]);
+        app.stats.collect_coins(3);
+        app.stats.collect_key();
+        app.stats.take_damage(30.0);
+        let before = app.stats;
+        let old_map = *app.world.get_map();
+
+        app.apply_action(Action::NewMazeKeepProgress);
+        assert_ne!(*app.world.get_map(), old_map);
+        assert_eq!((app.stats.coins, app.stats.keys, app.stats.health), (before.coins, before.keys, before.health));
+        assert_eq!(app.stats.score, before.score);
+        let pos = app.camera.position;
+        assert!(!app.world.is_wall(pos.x as i32, pos.y as i32));
+
+        // 普通的新迷宫清空收集进度，但保留生命值
+        app.regenerate_maze();
+        assert_eq!((app.stats.coins, app.stats.keys), (0, 0));
+        assert_eq!(app.stats.health, before.health);
+    }
+
+    #[test]
+    fn sprinting_is_faster_and_drains_stamina_until_it_recovers() {
+        let mut app = app(&[]);
+        app.camera = Camera::new(Vec2::new(25.5, 25.5), Vec2::new(1.0, 0.0));
+        app.camera.noclip = true;
+        let step = |app: &mut App, action: Action| {
+            let before = app.camera.position.x;
+            app.apply_action(action);
+            app.camera.position.x - before
+        };
+
+        let walk = step(&mut app, Action::Button(Button::Forward));
+        let run = step(&mut app, Action::Sprint(Button::Forward));
+        assert!((run / walk - SPRINT_MULTIPLIER).abs() < 1e-9);
+
+        app.update_stamina(0.1);
+        assert_eq!(app.stamina, MAX_STAMINA - SPRINT_DRAIN * 0.1);
+
+        // 耐力耗尽后按 Shift 也只是正常速度
+        app.stamina = 0.0;
+        let exhausted = step(&mut app, Action::Sprint(Button::Forward));
+        assert!((exhausted - walk).abs() < 1e-9);
+
+        app.sprint_time_left = 0.0;
+        app.update_stamina(1.0);
+        assert_eq!(app.stamina, STAMINA_REGEN);
+    }
+
+    #[test]
+    fn same_seed_places_items_and_npcs_identically() {
+        let layout = |app: &App| {
+            let items: Vec<_> = app.items.iter().map(|item| (item.x, item.y, item.item_type)).collect();
+            let npcs: Vec<_> = app.npcs.iter().map(|npc| (npc.x, npc.y, npc.dir_x, npc.dir_y)).collect();
+            (items, npcs)
+        };
+        let (first, second) = (app(&[]), app(&[]));
+        assert!(!first.items.is_empty());
+        assert_eq!(layout(&first), layout(&second));
+
+        // 换一个种子，布局也跟着变
+        let other = App::new(&Args::parse_from(["arsvt3d", "--seed", "8"]));
+        assert_ne!(layout(&first).0, layout(&other).0);
+    }
+
+    #[test]
+    fn walking_over_a_coin_emits_footsteps_then_a_pickup() {
+        let mut app = app(&[]);
+        app.npcs.clear();
+        let (x, y) = app
+            .world
+            .open_cells()
+            .find(|&(x, y)| !app.world.is_wall(x as i32 + 1, y as i32))
+            .unwrap();
+        app.camera = Camera::new(Vec2::new(x as f64 + 0.5, y as f64 + 0.5), Vec2::new(1.0, 0.0));
+        app.items = vec![Item::new(x as f64 + 1.5, y as f64 + 0.5, ItemType::Coin)];
+        app.drain_events();
+
+        let mut events = Vec::new();
+        for _ in 0..10 {
+            app.apply_action(Action::Button(Button::Forward));
+            app.tick(1.0 / 30.0);
+            events.extend(app.drain_events());
+        }
+        assert!(events.contains(&GameEvent::Footstep));
+        assert_eq!(events.iter().filter(|&&event| event == GameEvent::CoinPickup).count(), 1);
+
+        // 取走之后队列清空
+        assert!(app.drain_events().is_empty());
+        app.apply_action(Action::Jump);
+        assert_eq!(app.drain_events(), vec![GameEvent::Jump]);
+    }
+
+    #[test]
+    fn replaying_a_recording_reproduces_the_final_state() {
+        let mut original = app(&[]);
+        original.recording = Some(Recording::new(7, &Args::parse_from(["arsvt3d", "--seed", "7"])));
+        let script = [
+            Action::Button(Button::Forward),
+            Action::Rotate(0.4),
+            Action::Sprint(Button::Forward),
+            Action::Jump,
+            Action::Button(Button::StrafeRight),
+            Action::Rotate(-1.1),
+            Action::Button(Button::Forward),
+        ];
+        for (frame, &action) in script.iter().cycle().take(120).enumerate() {
+            original.apply_action(action);
+            original.tick(if frame % 3 == 0 { 1.0 / 20.0 } else { 1.0 / 45.0 });
+        }
+
+        let recording = original.recording.take().unwrap();
+        let mut args = Args::parse_from(["arsvt3d"]);
+        recording.apply_to(&mut args);
+        let mut replayed = App::new(&args);
+        for entry in recording.entries {
+            match entry {
+                ReplayEntry::Tick(delta_time) => replayed.tick(delta_time),
+                ReplayEntry::Action(action) => replayed.apply_action(action),
+            }
+        }
+
+        assert!(original.stats.steps > 0);
+        assert_eq!(replayed.camera.position, original.camera.position);
+        assert_eq!(replayed.camera.direction, original.camera.direction);
+        assert_eq!(
+            (replayed.stats.steps, replayed.stats.coins, replayed.stats.keys, replayed.stats.score),
+            (original.stats.steps, original.stats.coins, original.stats.keys, original.stats.score)
+        );
+        assert_eq!(replayed.stats.health, original.stats.health);
+    }
+
+    #[test]
+    fn exit_stays_locked_until_every_key_is_found() {
+        let mut app = app(&[]);
+        app.npcs.clear();
+        assert_eq!(app.difficulty.key_count(), 3);
+        let pos = app.camera.position;
+        let key = |x: f64, y: f64| Item::new(x, y, ItemType::Key);
+        app.items = vec![key(pos.x, pos.y), key(pos.x, pos.y), key(-10.0, -10.0), Item::new(pos.x, pos.y, ItemType::Exit)];
+
+        app.check_item_collection();
+        assert_eq!(app.stats.keys_found, 2);
+        assert!(app.exit_locked());
+        assert!(!app.items[3].collected);
+        let buffer = draw(&mut app);
+        assert!(text_in(&buffer, buffer.area).contains("Keys: 2/3"));
+
+        // 捡到第三把钥匙后，出口在下一次检测时可以进入
+        app.items[2] = key(pos.x, pos.y);
+        app.check_item_collection();
+        assert_eq!(app.stats.keys_found, 3);
+        assert!(!app.exit_locked());
+        app.check_item_collection();
+        assert!(app.items[3].collected);
+    }
+
+    #[test]
+    fn magnet_widens_the_pickup_radius_until_it_runs_out() {
+        let mut app = app(&[]);
+        app.npcs.clear();
+        let pos = app.camera.position;
+        let coin_at_one = || Item::new(pos.x + 1.0, pos.y, ItemType::Coin);
+
+        app.items = vec![coin_at_one()];
+        app.check_item_collection();
+        assert!(!app.items[0].collected, "coin at 1.0 picked up without a magnet");
+
+        app.items = vec![Item::new(pos.x, pos.y, ItemType::Magnet), coin_at_one()];
+        app.check_item_collection();
+        assert!(app.items[1].collected);
+        assert_eq!(app.stats.coins, 1);
+
+        // 时间用完后恢复原来的半径
+        app.update_magnet(MAGNET_DURATION);
+        assert_eq!(app.pickup_radius, PICKUP_RADIUS);
+        app.items = vec![coin_at_one()];
+        app.check_item_collection();
+        assert!(!app.items[0].collected);
+    }
+
+    #[test]
+    fn demo_mode_engages_when_idle_and_hands_back_on_input() {
+        let mut app = app(&["--demo-after", "5"]);
+        let start = app.camera.position;
+        app.update_idle();
+        assert!(!app.is_demo());
+
+        app.last_input = Instant::now() - Duration::from_secs(6);
+        app.update_idle();
+        assert!(app.is_demo());
+        for _ in 0..60 {
+            app.demo_tick(1.0 / 30.0);
+        }
+        assert_ne!(app.camera.position, start, "demo camera did not move");
+        assert_eq!(app.stats.steps, 0);
+
+        // 第一个输入被吞掉，镜头回到进入演示前的位置
+        assert!(app.note_input());
+        assert!(!app.is_demo());
+        assert_eq!(app.camera.position, start);
+        assert!(!app.note_input());
+        app.update_idle();
+        assert!(!app.is_demo());
+    }
+}
@@ -1,47 +1,60 @@
+use rand::seq::SliceRandom;
 use rand::Rng;
-use crate::world::WallType;
+use crate::world::{WallType, World};
 
 pub const MAP_WIDTH: usize = 51;
 pub const MAP_HEIGHT: usize = 51;
 
+// 超过这个格子数时默认改用显式栈挖路，避免递归过深
+const RECURSIVE_CELL_LIMIT: usize = 64 * 64;
+
 pub struct MazeGenerator {
     map: [[bool; MAP_HEIGHT]; MAP_WIDTH],
+    /// 用显式栈代替递归；两种方式消耗随机数的顺序相同，同一种子生成同一迷宫
+    pub iterative: bool,
+}
+
+impl Default for MazeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MazeGenerator {
     pub fn new() -> Self {
         MazeGenerator {
             map: [[true; MAP_HEIGHT]; MAP_WIDTH],
+            iterative: MAP_WIDTH * MAP_HEIGHT > RECURSIVE_CELL_LIMIT,
         }
     }
 
-    pub fn generate(&mut self) -> [[WallType; MAP_HEIGHT]; MAP_WIDTH] {
-        let mut rng = rand::thread_rng();
-        
+    pub fn generate(&mut self, rng: &mut impl Rng) -> [[WallType; MAP_HEIGHT]; MAP_WIDTH] {
         for x in 0..MAP_WIDTH {
             for y in 0..MAP_HEIGHT {
                 self.map[x][y] = true;
             }
         }
 
-        self.carve_path(1, 1, &mut rng);
+        if self.iterative {
+            self.carve_path_iterative(1, 1, rng);
+        } else {
+            self.carve_path(1, 1, rng);
+        }
 
         let mut result = [[WallType::Empty; MAP_HEIGHT]; MAP_WIDTH];
+        let palette: Vec<WallType> = WallType::all()
+            .iter()
+            .copied()
+            .filter(|wall| wall.is_solid() && *wall != WallType::Border && !wall.is_interactive())
+            .collect();
         
         for x in 0..MAP_WIDTH {
             for y in 0..MAP_HEIGHT {
                 if self.map[x][y] {
                     let wall_type = if x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1 {
-                        WallType::Red
+                        WallType::Border
                     } else {
-                        let pattern = (x / 5 + y / 5) % 5;
-                        match pattern {
-                            0 => WallType::Red,
-                            1 => WallType::Green,
-                            2 => WallType::Blue,
-                            3 => WallType::White,
-                            _ => WallType::Yellow,
-                        }
+                        palette[(x / 5 + y / 5) % palette.len()]
                     };
                     result[x][y] = wall_type;
                 }
@@ -51,45 +64,135 @@ impl MazeGenerator {
         result
     }
 
-    fn carve_path(&mut self, x: usize, y: usize, rng: &mut impl Rng) {
-        self.map[x][y] = false;
-
+    fn shuffled_directions(rng: &mut impl Rng) -> [(i32, i32); 4] {
         let mut directions = [(0, -2), (0, 2), (-2, 0), (2, 0)];
         
         for i in (1..directions.len()).rev() {
             let j = rng.gen_range(0..=i);
             directions.swap(i, j);
         }
+        directions
+    }
+
+    /// 从 (x, y) 朝 (dx, dy) 走两格，目标还是墙时打通中间的格子并返回目标
+    fn try_carve(&mut self, x: usize, y: usize, (dx, dy): (i32, i32)) -> Option<(usize, usize)> {
+        let nx = x as i32 + dx;
+        let ny = y as i32 + dy;
+
+        if nx > 0 && ny > 0 && nx < (MAP_WIDTH - 1) as i32 && ny < (MAP_HEIGHT - 1) as i32 {
+            let nx = nx as usize;
+            let ny = ny as usize;
+
+            if self.map[nx][ny] {
+                let mx = (x as i32 + dx / 2) as usize;
+                let my = (y as i32 + dy / 2) as usize;
+                self.map[mx][my] = false;
+                return Some((nx, ny));
+            }
+        }
+        None
+    }
+
+    fn carve_path(&mut self, x: usize, y: usize, rng: &mut impl Rng) {
+        self.map[x][y] = false;
 
-        for (dx, dy) in directions.iter() {
-            let nx = x as i32 + dx;
-            let ny = y as i32 + dy;
+        for direction in Self::shuffled_directions(rng) {
+            if let Some((nx, ny)) = self.try_carve(x, y, direction) {
+                self.carve_path(nx, ny, rng);
+            }
+        }
+    }
 
-            if nx > 0 && ny > 0 && nx < (MAP_WIDTH - 1) as i32 && ny < (MAP_HEIGHT - 1) as i32 {
-                let nx = nx as usize;
-                let ny = ny as usize;
+    fn carve_path_iterative(&mut self, x: usize, y: usize, rng: &mut impl Rng) {
+        self.map[x][y] = false;
 
-                if self.map[nx][ny] {
-                    let mx = (x as i32 + dx / 2) as usize;
-                    let my = (y as i32 + dy / 2) as usize;
-                    self.map[mx][my] = false;
-                    
-                    self.carve_path(nx, ny, rng);
+        // 每一帧记录格子、打乱后的方向和下一个要尝试的方向
+        let mut stack = vec![(x, y, Self::shuffled_directions(rng), 0)];
+        while let Some(frame) = stack.last_mut() {
+            let (cx, cy, directions, next) = *frame;
+            if next == directions.len() {
+                stack.pop();
+                continue;
+            }
+            frame.3 += 1;
+
+            if let Some((nx, ny)) = self.try_carve(cx, cy, directions[next]) {
+                self.map[nx][ny] = false;
+                stack.push((nx, ny, Self::shuffled_directions(rng), 0));
+            }
+        }
+    }
+
+    pub fn get_start_position(world: &World, rng: &mut impl Rng) -> (f64, f64) {
+        let cells: Vec<(usize, usize)> = world.open_cells().collect();
+        match cells.choose(rng) {
+            Some(&(x, y)) => (x as f64 + 0.5, y as f64 + 0.5),
+            // 迷宫从 (1,1) 开始挖，不会没有空格，这里只是兜底
+            None => (1.5, 1.5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn generate(iterative: bool, seed: u64) -> [[WallType; MAP_HEIGHT]; MAP_WIDTH] {
+        let mut generator = MazeGenerator::new();
+        generator.iterative = iterative;
+        generator.generate(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// 从 (1, 1) 出发能走到所有空格，并且每个奇数坐标的格子都被挖开了
+    fn assert_connected(map: &[[WallType; MAP_HEIGHT]; MAP_WIDTH]) {
+        let mut seen = vec![vec![false; MAP_HEIGHT]; MAP_WIDTH];
+        let mut stack = vec![(1, 1)];
+        seen[1][1] = true;
+        while let Some((x, y)) = stack.pop() {
+            for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if map[nx][ny] == WallType::Empty && !seen[nx][ny] {
+                    seen[nx][ny] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                assert_eq!(map[x][y] == WallType::Empty, seen[x][y], "cell ({}, {})", x, y);
+                if x % 2 == 1 && y % 2 == 1 {
+                    assert!(seen[x][y], "cell ({}, {}) was never carved", x, y);
                 }
             }
         }
     }
 
-    pub fn get_start_position(&self) -> (f64, f64) {
-        let mut rng = rand::thread_rng();
-        
-        loop {
-            let x = rng.gen_range(1..MAP_WIDTH - 1);
-            let y = rng.gen_range(1..MAP_HEIGHT - 1);
-            
-            if !self.map[x][y] {
-                return (x as f64 + 0.5, y as f64 + 0.5);
+    #[test]
+    fn recursive_and_iterative_carvers_both_connect_the_maze() {
+        for seed in 0..5 {
+            let recursive = generate(false, seed);
+            let iterative = generate(true, seed);
+            assert_connected(&recursive);
+            assert_connected(&iterative);
+            assert_eq!(recursive, iterative);
+        }
+    }
+
+    #[test]
+    fn only_the_perimeter_uses_border_walls() {
+        for iterative in [false, true] {
+            let map = generate(iterative, 11);
+            for (x, column) in map.iter().enumerate() {
+                for (y, &wall) in column.iter().enumerate() {
+                    let perimeter = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+                    assert_eq!(wall == WallType::Border, perimeter, "cell ({}, {}) is {:?}", x, y, wall);
+                }
             }
         }
+
+        // 小地图和 3D 视图里都和普通红墙区分开
+        assert_ne!(WallType::Border.minimap_glyph(), WallType::Red.minimap_glyph());
+        assert_ne!(WallType::Border.base_rgb(), WallType::Red.base_rgb());
     }
 }
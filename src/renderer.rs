@@ -2,17 +2,176 @@ use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, BorderType, Paragraph},
+    widgets::{Block, Borders, BorderType, Clear, Paragraph},
     Frame,
 };
 
 use crate::camera::Camera;
+use crate::color::ColorMode;
 use crate::world::{World, WallType};
-use crate::entities::{Item, NPC};
+use crate::entities::{Item, ItemType, NPCType, NPC};
+
+// 比这个距离更近的物品会在地面上画出影子
+const SHADOW_DISTANCE: f64 = 8.0;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum WallTexture {
+    #[default]
+    Brick,
+    Smooth,
+    Panel,
+}
+
+impl WallTexture {
+    pub fn next(self) -> Self {
+        match self {
+            WallTexture::Brick => WallTexture::Smooth,
+            WallTexture::Smooth => WallTexture::Panel,
+            WallTexture::Panel => WallTexture::Brick,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum CeilingStyle {
+    #[default]
+    Gradient,
+    Stars,
+    SolidColor,
+}
+
+impl CeilingStyle {
+    pub fn next(self) -> Self {
+        match self {
+            CeilingStyle::Gradient => CeilingStyle::Stars,
+            CeilingStyle::Stars => CeilingStyle::SolidColor,
+            CeilingStyle::SolidColor => CeilingStyle::Gradient,
+        }
+    }
+}
+
+/// 墙在半行单位下覆盖 [start_half, end_half)，只覆盖了 row 行一半时返回对应的半块字符
+fn edge_glyph(start_half: i64, end_half: i64, row: i64) -> Option<char> {
+    let covers = |half: i64| half >= start_half && half < end_half;
+    match (covers(2 * row), covers(2 * row + 1)) {
+        (true, false) => Some('▀'),
+        (false, true) => Some('▄'),
+        _ => None,
+    }
+}
+
+/// 把方块、线框和 emoji 换成明暗相近的 ASCII 字符（明暗梯度 ` .:-=+*#@`）
+fn ascii_fallback(ch: char) -> char {
+    if ch.is_ascii() {
+        return ch;
+    }
+    match ch {
+        '█' => '@',
+        '▓' => '#',
+        '▒' => '*',
+        '░' => '+',
+        '═' => '=',
+        '·' => '.',
+        '▁' | '▄' => '_',
+        '▀' => '-',
+        '◆' => '$',
+        '🔑' => 'k',
+        '❤' => 'h',
+        '🚪' => 'E',
+        '📡' => 'r',
+        '🧲' => 'm',
+        '◉' => 'o',
+        '→' => '>',
+        '←' => '<',
+        '↑' => '^',
+        '↓' => 'v',
+        '↗' | '↙' => '/',
+        '↖' | '↘' => '\\',
+        '▲' => '^',
+        '▼' => 'v',
+        '◄' | '↺' => '<',
+        '►' | '↻' => '>',
+        '⊡' => '=',
+        '⚠' => '!',
+        '✓' => '+',
+        '•' => '-',
+        '⚡' => '*',
+        _ => ':',
+    }
+}
+
+/// HUD 文字的 ASCII 版本：标题前装饰用的 emoji 连同后面的空格一起去掉，其余字符按 `ascii_fallback` 替换
+fn ascii_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '🎮' | '📊' | 'ℹ' | '🖱' | '⌨' | '🗺' | '🔄' => {
+                while matches!(chars.peek(), Some('\u{fe0f}' | ' ')) {
+                    chars.next();
+                }
+            }
+            '\u{fe0f}' | '°' => {}
+            _ => out.push(ascii_fallback(ch)),
+        }
+    }
+    out
+}
+
+/// 环境变量里的 locale 明确不是 UTF-8（例如 LANG=C）时认为终端不支持 Unicode，没有设置时按支持处理
+pub fn unicode_supported() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+        .unwrap_or(true)
+}
+
+/// 根据屏幕坐标确定性地决定星星，每帧位置相同不会闪烁
+fn star_at(x: usize, y: usize) -> char {
+    let hash = (x as u32).wrapping_mul(73_856_093) ^ (y as u32).wrapping_mul(19_349_663);
+    match hash % 61 {
+        0 => '*',
+        1..=3 => '·',
+        _ => ' ',
+    }
+}
+
+/// 画一帧需要的场景：从哪个镜头看、看哪张地图，以及地图上的物品和NPC
+#[derive(Clone, Copy)]
+pub struct RenderContext<'a> {
+    pub camera: &'a Camera,
+    pub world: &'a World,
+    pub items: &'a [Item],
+    pub npcs: &'a [NPC],
+    pub monochrome_mode: bool,
+}
 
 pub struct Renderer {
     buffer: Vec<Vec<char>>,
     color_buffer: Vec<Vec<Color>>,
+    // 每一列当前最近物体的距离，精灵只在比它更近时才覆盖
+    depth_buffer: Vec<f64>,
+    pub wall_texture: WallTexture,
+    pub ceiling_style: CeilingStyle,
+    // 输出时把颜色降级到终端支持的调色板
+    pub color_mode: ColorMode,
+    // 墙的上下边缘用半块字符绘制，垂直精度翻倍；有些终端半块显示不好，默认关闭
+    pub smooth_edges: bool,
+    // 色盲友好配色：墙和实体不再依赖红绿区分
+    pub color_blind: bool,
+    // 只输出 ASCII 字符，用于不支持 Unicode 的终端
+    pub ascii_only: bool,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Renderer {
@@ -20,23 +179,61 @@ impl Renderer {
         Renderer {
             buffer: Vec::new(),
             color_buffer: Vec::new(),
+            depth_buffer: Vec::new(),
+            wall_texture: WallTexture::default(),
+            ceiling_style: CeilingStyle::default(),
+            color_mode: ColorMode::TrueColor,
+            smooth_edges: false,
+            color_blind: false,
+            ascii_only: false,
         }
     }
 
     fn resize_buffers(&mut self, width: usize, height: usize) {
-        if self.buffer.len() != height || (self.buffer.len() > 0 && self.buffer[0].len() != width) {
+        if self.buffer.len() != height || (!self.buffer.is_empty() && self.buffer[0].len() != width) {
             self.buffer = vec![vec![' '; width]; height];
             self.color_buffer = vec![vec![Color::Black; width]; height];
         }
     }
 
-    fn clear(&mut self, width: usize, height: usize) {
+    /// 天花板结束行和地板起始行，随视角俯仰一起移动，但两者都至少留一行在屏幕内
+    fn horizon_bands(height: usize, horizon_offset: i32) -> (usize, usize) {
+        let h = height as i32;
+        let offset = horizon_offset.clamp(1 - h / 3, (h - 1 - h * 2 / 3).max(1 - h / 3));
+        let ceiling_end = (h / 3 + offset).clamp(0, h) as usize;
+        let floor_start = (h * 2 / 3 + offset).clamp(0, h) as usize;
+        (ceiling_end, floor_start)
+    }
+
+    /// 距离为 depth 的物体与地面接触的屏幕行，和同距离墙壁的底边对齐
+    fn floor_row(height: usize, depth: f64, horizon_offset: i32) -> usize {
+        let half_wall = ((height as f64 / depth) as usize).min(height * 4) / 2;
+        ((height / 2 + half_wall) as i32 + horizon_offset).clamp(0, (height as i32 - 1).max(0)) as usize
+    }
+
+    /// 悬在视平线附近的精灵（NPC）所在的行，和墙一样随 horizon_offset 整体平移，移出屏幕时返回 None
+    fn eye_level_row(height: usize, depth: f64, horizon_offset: i32) -> Option<usize> {
+        let sprite_height = ((height as f64 / depth) as usize).min(height / 2);
+        let row = (height / 2).saturating_sub(sprite_height / 4) as i32 + horizon_offset;
+        (row >= 0 && (row as usize) < height).then_some(row as usize)
+    }
+
+    fn clear(&mut self, width: usize, height: usize, horizon_offset: i32) {
         self.resize_buffers(width, height);
+        let (ceiling_end, floor_start) = Self::horizon_bands(height, horizon_offset);
+        let ceiling_shift = ceiling_end as f64 - (height / 3) as f64;
         
         for y in 0..height {
             for x in 0..width {
-                if y < height / 3 {
-                    let ceiling_depth = y as f64 / (height as f64 / 3.0);
+                if y < ceiling_end && self.ceiling_style == CeilingStyle::Stars {
+                    let star = star_at(x, y);
+                    self.buffer[y][x] = star;
+                    self.color_buffer[y][x] = if star == '*' { Color::Rgb(230, 230, 255) } else { Color::Rgb(140, 140, 180) };
+                } else if y < ceiling_end && self.ceiling_style == CeilingStyle::SolidColor {
+                    self.buffer[y][x] = '█';
+                    self.color_buffer[y][x] = Color::Rgb(20, 20, 45);
+                } else if y < ceiling_end {
+                    let ceiling_depth = (y as f64 - ceiling_shift) / (height as f64 / 3.0);
                     let ceiling_brightness = (0.1 + ceiling_depth * 0.15) as u8;
                     self.buffer[y][x] = match ceiling_brightness {
                         0..=5 => ' ',
@@ -49,8 +246,8 @@ impl Renderer {
                         20 + ceiling_brightness,
                         40 + ceiling_brightness * 2
                     );
-                } else if y >= height * 2 / 3 {
-                    let floor_y = y - height * 2 / 3;
+                } else if y >= floor_start {
+                    let floor_y = y - floor_start;
                     let floor_depth = (height / 3) as f64 / (floor_y as f64 + 1.0);
                     let floor_brightness = (1.0 / (1.0 + floor_depth * 0.2)).clamp(0.0, 1.0);
                     
@@ -80,15 +277,66 @@ impl Renderer {
         }
     }
 
+    fn wall_rgb(&self, wall_type: WallType) -> (u8, u8, u8) {
+        if self.color_blind {
+            wall_type.color_blind_rgb()
+        } else {
+            wall_type.base_rgb()
+        }
+    }
+
+    fn item_color(&self, item_type: ItemType) -> Color {
+        match (self.color_blind, item_type) {
+            (false, ItemType::Coin) => Color::Yellow,
+            (false, ItemType::Key) => Color::Cyan,
+            (false, ItemType::Health) => Color::Red,
+            (false, ItemType::Exit) => Color::Green,
+            (false, ItemType::Radar) => Color::Magenta,
+            (false, ItemType::Magnet) => Color::LightBlue,
+            // 色盲配色下生命和出口不再依赖红绿区分
+            (true, ItemType::Coin) => Color::Rgb(240, 228, 66),
+            (true, ItemType::Key) => Color::Rgb(86, 180, 233),
+            (true, ItemType::Health) => Color::Rgb(213, 94, 0),
+            (true, ItemType::Exit) => Color::Rgb(0, 114, 178),
+            (true, ItemType::Radar) => Color::Rgb(204, 121, 167),
+            (true, ItemType::Magnet) => Color::Rgb(0, 158, 115),
+        }
+    }
+
+    /// 物品的字符，3D 视图、小地图和图例共用
+    fn item_glyph(item_type: ItemType) -> char {
+        match item_type {
+            ItemType::Coin => '◆',
+            ItemType::Key => '🔑',
+            ItemType::Health => '❤',
+            ItemType::Exit => '🚪',
+            ItemType::Radar => '📡',
+            ItemType::Magnet => '🧲',
+        }
+    }
+
+    /// NPC的字符，3D 视图、小地图和图例共用
+    fn npc_glyph(npc_type: NPCType, alert: bool) -> &'static str {
+        match npc_type {
+            NPCType::Wanderer => "T^T",
+            NPCType::Guard if alert => "(!.!)",
+            NPCType::Guard => "(^.^)",
+        }
+    }
+
+    fn npc_color(&self, npc_type: NPCType, alert: bool) -> Color {
+        match (self.color_blind, npc_type) {
+            (false, NPCType::Wanderer) => Color::LightGreen,
+            (false, NPCType::Guard) if alert => Color::Red,
+            (false, NPCType::Guard) => Color::LightRed,
+            (true, NPCType::Wanderer) => Color::Rgb(86, 180, 233),
+            (true, NPCType::Guard) if alert => Color::Rgb(213, 94, 0),
+            (true, NPCType::Guard) => Color::Rgb(230, 159, 0),
+        }
+    }
+
     fn get_wall_color(&self, wall_type: WallType, brightness: f64, distance: f64) -> Color {
-        let base = match wall_type {
-            WallType::Red => (255, 80, 80),
-            WallType::Green => (80, 255, 80),
-            WallType::Blue => (100, 160, 255),
-            WallType::White => (255, 255, 255),
-            WallType::Yellow => (255, 255, 80),
-            WallType::Empty => (128, 128, 128),
-        };
+        let base = self.wall_rgb(wall_type);
         
         let fog_factor = (1.0 / (1.0 + distance * 0.08)).clamp(0.0, 1.0);
         let bright = (brightness * fog_factor).clamp(0.1, 1.0);
@@ -106,12 +354,20 @@ impl Renderer {
         let brightness = 1.0 / (1.0 + distance * distance * 0.025);
         let adjusted = if side { brightness * 0.7 } else { brightness };
         
-        let brick_x = (wall_x * 4.0) as usize % 4;
-        let brick_y = (y_ratio * 6.0) as usize % 6;
-        
-        let is_mortar_h = brick_y == 0 || brick_y == 3;
-        let is_mortar_v = brick_x == 0;
-        let is_edge = y_ratio < 0.05 || y_ratio > 0.95;
+        let (is_mortar_h, is_mortar_v) = match self.wall_texture {
+            WallTexture::Brick => {
+                let brick_x = (wall_x * 4.0) as usize % 4;
+                let brick_y = (y_ratio * 6.0) as usize % 6;
+                (brick_y == 0 || brick_y == 3, brick_x == 0)
+            }
+            WallTexture::Smooth => (false, false),
+            WallTexture::Panel => {
+                // 每格墙分成左右两块面板，中间一道横缝
+                let panel_x = (wall_x * 8.0) as usize % 4;
+                (y_ratio > 0.47 && y_ratio < 0.53, panel_x == 0)
+            }
+        };
+        let is_edge = self.wall_texture != WallTexture::Smooth && !(0.05..=0.95).contains(&y_ratio);
         
         if adjusted > 0.75 {
             if is_edge {
@@ -140,107 +396,75 @@ impl Renderer {
         }
     }
 
-    pub fn render(&mut self, frame: &mut Frame, area: Rect, camera: &Camera, world: &World, items: &[Item], npcs: &[NPC], monochrome_mode: bool) {
-        let width = area.width.saturating_sub(2) as usize;
-        let height = area.height.saturating_sub(2) as usize;
-        
-        if width == 0 || height == 0 {
-            return;
-        }
-        
-        self.clear(width, height);
+    /// 不依赖终端，把一帧画面渲染进内部缓冲区并返回字符网格
+    pub fn render_to_buffer(&mut self, width: usize, height: usize, ctx: &RenderContext) -> &[Vec<char>] {
+        let RenderContext { camera, world, items, npcs, monochrome_mode } = *ctx;
+        let horizon_offset = camera.get_horizon_offset();
+        self.clear(width, height, horizon_offset);
+
+        self.depth_buffer.clear();
+        self.depth_buffer.resize(width, f64::INFINITY);
 
         let pos = camera.position;
         let dir = camera.direction;
         let plane = camera.plane;
-        let horizon_offset = camera.get_horizon_offset();
 
         for x in 0..width {
             let camera_x = 2.0 * x as f64 / width as f64 - 1.0;
-            let ray_dir_x = dir.x + plane.x * camera_x;
-            let ray_dir_y = dir.y + plane.y * camera_x;
-
-            let mut map_x = pos.x as i32;
-            let mut map_y = pos.y as i32;
+            let ray_dir = dir + plane * camera_x;
 
-            let delta_dist_x = if ray_dir_x.abs() < 1e-10 {
-                1e30
-            } else {
-                (1.0 / ray_dir_x).abs()
-            };
-            
-            let delta_dist_y = if ray_dir_y.abs() < 1e-10 {
-                1e30
-            } else {
-                (1.0 / ray_dir_y).abs()
+            let Some(hit) = world.cast_ray(pos, ray_dir) else {
+                continue;
             };
+            let (map_x, map_y) = hit.cell;
+            let side = hit.side;
+            let wall_x = hit.wall_x;
+            let perp_wall_dist = hit.distance.max(0.01);
+            self.depth_buffer[x] = perp_wall_dist;
 
-            let (step_x, mut side_dist_x) = if ray_dir_x < 0.0 {
-                (-1, (pos.x - map_x as f64) * delta_dist_x)
+            // 开启平滑边缘时按半行计算墙的范围
+            let half_span = if self.smooth_edges {
+                let line_height = ((2.0 * height as f64 / perp_wall_dist) as i64).min(height as i64 * 8);
+                let center = height as i64 + 2 * horizon_offset as i64;
+                Some((center - line_height / 2, center + line_height / 2))
             } else {
-                (1, (map_x as f64 + 1.0 - pos.x) * delta_dist_x)
+                None
             };
 
-            let (step_y, mut side_dist_y) = if ray_dir_y < 0.0 {
-                (-1, (pos.y - map_y as f64) * delta_dist_y)
-            } else {
-                (1, (map_y as f64 + 1.0 - pos.y) * delta_dist_y)
-            };
+            let (draw_start, draw_end) = match half_span {
+                Some((start_half, end_half)) => (
+                    start_half.div_euclid(2).clamp(0, height as i64) as usize,
+                    (end_half + 1).div_euclid(2).clamp(0, height as i64) as usize,
+                ),
+                None => {
+                    let line_height = ((height as f64 / perp_wall_dist) as usize).min(height * 4);
 
-            let mut hit = false;
-            let mut side = false;
-            let mut iterations = 0;
+                    let draw_start_base = (height / 2).saturating_sub(line_height / 2);
+                    let draw_end_base = ((height / 2) + (line_height / 2)).min(height);
 
-            while !hit && iterations < 100 {
-                if side_dist_x < side_dist_y {
-                    side_dist_x += delta_dist_x;
-                    map_x += step_x;
-                    side = false;
-                } else {
-                    side_dist_y += delta_dist_y;
-                    map_y += step_y;
-                    side = true;
+                    (
+                        ((draw_start_base as i32 + horizon_offset).max(0) as usize).min(height),
+                        ((draw_end_base as i32 + horizon_offset).max(0) as usize).min(height),
+                    )
                 }
-
-                if world.is_wall(map_x, map_y) {
-                    hit = true;
-                }
-                iterations += 1;
-            }
-
-            if !hit {
-                continue;
-            }
-
-            let perp_wall_dist = if !side {
-                (side_dist_x - delta_dist_x).max(0.01)
-            } else {
-                (side_dist_y - delta_dist_y).max(0.01)
             };
 
-            let wall_x = if !side {
-                pos.y + perp_wall_dist * ray_dir_y
+            let wall_type = hit.wall_type;
+            // 墙面亮度取决于射线撞墙前所在格子的光照
+            let (lit_x, lit_y) = if side {
+                (map_x, map_y - ray_dir.y.signum() as i32)
             } else {
-                pos.x + perp_wall_dist * ray_dir_x
+                (map_x - ray_dir.x.signum() as i32, map_y)
             };
-            let wall_x = wall_x - wall_x.floor();
-
-            let line_height = ((height as f64 / perp_wall_dist) as usize).min(height * 4);
-
-            let draw_start_base = (height / 2).saturating_sub(line_height / 2);
-            let draw_end_base = ((height / 2) + (line_height / 2)).min(height);
-            
-            let draw_start = ((draw_start_base as i32 + horizon_offset).max(0) as usize).min(height);
-            let draw_end = ((draw_end_base as i32 + horizon_offset).max(0) as usize).min(height);
-
-            let wall_type = world.get(map_x, map_y);
-            let brightness = 1.0 / (1.0 + perp_wall_dist * perp_wall_dist * 0.03);
+            let light = world.tile(lit_x, lit_y).light_level;
+            let brightness = light / (1.0 + perp_wall_dist * perp_wall_dist * 0.03);
             let adjusted_brightness = if side { brightness * 0.65 } else { brightness };
 
             for y in draw_start..draw_end {
                 if y < height && x < width {
                     let y_ratio = (y as f64 - draw_start as f64) / (draw_end - draw_start).max(1) as f64;
-                    let ch = self.get_char(perp_wall_dist, side, wall_x, y_ratio);
+                    let edge = half_span.and_then(|(start_half, end_half)| edge_glyph(start_half, end_half, y as i64));
+                    let ch = edge.unwrap_or_else(|| self.get_char(perp_wall_dist, side, wall_x, y_ratio));
                     let color = if monochrome_mode {
                         // 纯色模式：所有物体都使用白色
                         let brightness = adjusted_brightness.clamp(0.2, 1.0);
@@ -258,7 +482,8 @@ impl Renderer {
             }
         }
 
-        let mut sprite_order: Vec<(usize, f64, String, Color)> = Vec::new();
+        // 屏幕列、深度、图标、颜色、是否贴地绘制（物品放在地上，NPC 悬在视平线附近）
+        let mut sprite_order: Vec<(usize, f64, String, Color, bool)> = Vec::new();
         
         for item in items {
             if item.collected {
@@ -274,24 +499,14 @@ impl Renderer {
             if transform_y > 0.1 && transform_y < 20.0 {
                 let sprite_screen_x = ((width as f64 / 2.0) * (1.0 + transform_x / transform_y)) as i32;
                 if sprite_screen_x > 0 && sprite_screen_x < width as i32 {
-                    let icon = match item.item_type {
-                        crate::entities::ItemType::Coin => "◆",
-                        crate::entities::ItemType::Key => "🔑",
-                        crate::entities::ItemType::Health => "❤",
-                        crate::entities::ItemType::Exit => "🚪",
-                    };
+                    let icon = Self::item_glyph(item.item_type);
                     let color = if monochrome_mode {
                         // 纯色模式：所有物品都使用白色
                         Color::White
                     } else {
-                        match item.item_type {
-                            crate::entities::ItemType::Coin => Color::Yellow,
-                            crate::entities::ItemType::Key => Color::Cyan,
-                            crate::entities::ItemType::Health => Color::Red,
-                            crate::entities::ItemType::Exit => Color::Green,
-                        }
+                        self.item_color(item.item_type)
                     };
-                    sprite_order.push((sprite_screen_x as usize, transform_y, icon.to_string(), color));
+                    sprite_order.push((sprite_screen_x as usize, transform_y, icon.to_string(), color, true));
                 }
             }
         }
@@ -307,67 +522,214 @@ impl Renderer {
             if transform_y > 0.1 && transform_y < 20.0 {
                 let sprite_screen_x = ((width as f64 / 2.0) * (1.0 + transform_x / transform_y)) as i32;
                 if sprite_screen_x > 0 && sprite_screen_x < width as i32 {
-                    let icon = match npc.npc_type {
-                        crate::entities::NPCType::Wanderer => "T^T",
-                        crate::entities::NPCType::Guard => "(^.^)",
-                    };
+                    let icon = Self::npc_glyph(npc.npc_type, npc.alert);
                     let color = if monochrome_mode {
                         // 纯色模式：所有NPC都使用白色
                         Color::White
                     } else {
-                        match npc.npc_type {
-                            crate::entities::NPCType::Wanderer => Color::LightGreen,
-                            crate::entities::NPCType::Guard => Color::LightRed,
-                        }
+                        self.npc_color(npc.npc_type, npc.alert)
                     };
-                    sprite_order.push((sprite_screen_x as usize, transform_y, icon.to_string(), color));
+                    sprite_order.push((sprite_screen_x as usize, transform_y, icon.to_string(), color, false));
                 }
             }
         }
         
         sprite_order.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
         
-        for (screen_x, depth, icon, color) in sprite_order {
+        for (screen_x, depth, icon, color, on_floor) in sprite_order {
             if screen_x < width {
-                let sprite_height = ((height as f64 / depth) as usize).min(height / 2);
-                let draw_y = ((height / 2).saturating_sub(sprite_height / 4) as isize + horizon_offset.max(-20).min(20) as isize).max(0) as usize;
+                let draw_y = if on_floor {
+                    let floor_y = Self::floor_row(height, depth, horizon_offset);
+                    // 近处的物品在脚下画一道影子，物品本身放在影子上方一行
+                    if depth < SHADOW_DISTANCE && floor_y > 0 {
+                        for i in 0..icon.chars().count() {
+                            let current_x = screen_x + i;
+                            if current_x < width && depth <= self.depth_buffer[current_x] {
+                                self.buffer[floor_y][current_x] = '▁';
+                                self.color_buffer[floor_y][current_x] = Color::DarkGray;
+                            }
+                        }
+                        floor_y - 1
+                    } else {
+                        floor_y
+                    }
+                } else {
+                    match Self::eye_level_row(height, depth, horizon_offset) {
+                        Some(row) => row,
+                        None => continue,
+                    }
+                };
                 
                 if draw_y < height {
                     // 绘制多字符图标，每个字符占据一个屏幕位置
                     for (i, ch) in icon.chars().enumerate() {
                         let current_x = screen_x + i;
-                        if current_x < width {
+                        // 被墙挡住或已有更近的精灵时不画
+                        if current_x < width && depth <= self.depth_buffer[current_x] {
                             self.buffer[draw_y][current_x] = ch;
                             self.color_buffer[draw_y][current_x] = color;
+                            self.depth_buffer[current_x] = depth;
                         }
                     }
                 }
             }
         }
 
-        let lines: Vec<Line> = self.buffer.iter().enumerate().map(|(y, row)| {
+        if self.ascii_only {
+            for ch in self.buffer.iter_mut().flatten() {
+                *ch = ascii_fallback(*ch);
+            }
+        }
+        &self.buffer
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, ctx: &RenderContext) {
+        let width = area.width.saturating_sub(2) as usize;
+        let height = area.height.saturating_sub(2) as usize;
+        
+        if width == 0 || height == 0 {
+            return;
+        }
+        
+        self.render_to_buffer(width, height, ctx);
+        let lines = self.buffer_lines();
+
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .title(vec![
+                    Span::styled(self.display_text("═══ "), Style::default().fg(Color::DarkGray)),
+                    Span::styled(self.display_text("🎮 3D VIEW "), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                    Span::styled(self.display_text("═══"), Style::default().fg(Color::DarkGray)),
+                ]));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn display_char(&self, ch: char) -> char {
+        if self.ascii_only {
+            ascii_fallback(ch)
+        } else {
+            ch
+        }
+    }
+
+    /// 界面上的文字，`ascii_only` 时换成 ASCII
+    pub fn display_text(&self, text: &str) -> String {
+        if self.ascii_only {
+            ascii_text(text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// 对每一段文字套用 `display_text`，保留原来的样式
+    pub fn display_lines(&self, mut lines: Vec<Line<'static>>) -> Vec<Line<'static>> {
+        if self.ascii_only {
+            for span in lines.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+                span.content = ascii_text(&span.content).into();
+            }
+        }
+        lines
+    }
+
+    /// 把缓冲区转换成带颜色的文本行
+    fn buffer_lines(&self) -> Vec<Line<'static>> {
+        self.buffer.iter().enumerate().map(|(y, row)| {
             let spans: Vec<Span> = row.iter().enumerate().map(|(x, &ch)| {
                 Span::styled(
                     ch.to_string(), 
-                    Style::default().fg(self.color_buffer[y][x])
+                    Style::default().fg(self.color_mode.apply(self.color_buffer[y][x]))
                 )
             }).collect();
             Line::from(spans)
-        }).collect();
+        }).collect()
+    }
+
+    /// 在 3D 视图右上角画一个后视镜小窗：方向和相机平面取反后再投射一次
+    pub fn render_rear_view(&mut self, frame: &mut Frame, area: Rect, ctx: &RenderContext) {
+        let inset_width = (area.width / 4).max(12);
+        let inset_height = (area.height / 4).max(6);
+        if area.width < inset_width + 2 || area.height < inset_height + 2 {
+            return;
+        }
+        let inset = Rect::new(area.x + area.width - inset_width - 1, area.y + 1, inset_width, inset_height);
+
+        let camera = ctx.camera;
+        let mut rear = Camera::new(camera.position, -camera.direction);
+        rear.plane = -camera.plane;
+        rear.pitch = camera.pitch;
+        rear.z_position = camera.z_position;
+
+        let width = inset.width.saturating_sub(2) as usize;
+        let height = inset.height.saturating_sub(2) as usize;
+        let rear_ctx = RenderContext { camera: &rear, ..*ctx };
+        self.render_to_buffer(width, height, &rear_ctx);
+        let lines = self.buffer_lines();
 
         let paragraph = Paragraph::new(lines)
             .block(Block::default()
                 .borders(Borders::ALL)
-                .border_type(BorderType::Double)
-                .title(vec![
-                    Span::styled("═══ ", Style::default().fg(Color::DarkGray)),
-                    Span::styled("🎮 3D VIEW ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-                    Span::styled("═══", Style::default().fg(Color::DarkGray)),
-                ]));
-        frame.render_widget(paragraph, area);
+                .border_type(BorderType::Rounded)
+                .title(Span::styled("Rear", Style::default().fg(Color::Cyan))));
+        frame.render_widget(Clear, inset);
+        frame.render_widget(paragraph, inset);
     }
 
-    pub fn render_minimap(&self, frame: &mut Frame, area: Rect, camera: &Camera, world: &World, items: &[Item], npcs: &[NPC], monochrome_mode: bool) {
+    /// 小地图图例：每个字符对应的含义，字符和颜色与 `render_minimap` 一致
+    pub fn minimap_legend_lines(&self, monochrome_mode: bool) -> Vec<Line<'static>> {
+        let entry = |glyph: String, color: Color, label: &str| {
+            let color = if monochrome_mode { Color::White } else { self.color_mode.apply(color) };
+            Line::from(vec![
+                Span::styled(format!("{:<5} ", glyph), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::raw(label.to_string()),
+            ])
+        };
+
+        let mut lines = vec![
+            entry(self.display_char('→').to_string(), Color::Red, "You (facing)"),
+            entry(self.display_char('◉').to_string(), Color::Yellow, "Around you"),
+            entry(self.display_char('·').to_string(), Color::DarkGray, "Visited"),
+            entry("*".to_string(), Color::White, "Several things"),
+        ];
+        let items = [
+            (ItemType::Coin, "Coin"),
+            (ItemType::Key, "Key"),
+            (ItemType::Health, "Health"),
+            (ItemType::Exit, "Exit"),
+            (ItemType::Radar, "Radar"),
+            (ItemType::Magnet, "Magnet"),
+        ];
+        for (item_type, label) in items {
+            lines.push(entry(self.display_char(Self::item_glyph(item_type)).to_string(), self.item_color(item_type), label));
+        }
+        let npcs = [
+            (NPCType::Wanderer, false, "Wanderer"),
+            (NPCType::Guard, false, "Guard"),
+            (NPCType::Guard, true, "Guard (alert)"),
+        ];
+        for (npc_type, alert, label) in npcs {
+            lines.push(entry(Self::npc_glyph(npc_type, alert).to_string(), self.npc_color(npc_type, alert), label));
+        }
+        for &wall in WallType::all().iter().filter(|wall| **wall != WallType::Empty) {
+            let (r, g, b) = self.wall_rgb(wall);
+            lines.push(entry(wall.minimap_glyph().to_string(), Color::Rgb(r, g, b), wall.name()));
+        }
+        lines
+    }
+
+    pub fn render_minimap_legend(&self, frame: &mut Frame, area: Rect, monochrome_mode: bool) {
+        let legend = Paragraph::new(self.minimap_legend_lines(monochrome_mode))
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(Span::styled("Legend", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))));
+        frame.render_widget(legend, area);
+    }
+
+    /// 小地图；`ctx.npcs` 为空时不标出NPC
+    pub fn render_minimap(&self, frame: &mut Frame, area: Rect, ctx: &RenderContext, visited: &[Vec<bool>], radar_radius: Option<f64>) {
+        let RenderContext { camera, world, items, npcs, monochrome_mode } = *ctx;
         let map = world.get_map();
         let view_size = 24;
         
@@ -402,28 +764,44 @@ impl Renderer {
                         _ => '↘',
                     };
                     spans.push(Span::styled(
-                        dir_char.to_string(), 
+                        self.display_char(dir_char).to_string(), 
                         Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
                     ));
                 } else if dist_sq <= 4 {
-                    spans.push(Span::styled("◉", Style::default().fg(Color::Yellow)));
+                    spans.push(Span::styled(self.display_char('◉').to_string(), Style::default().fg(Color::Yellow)));
                 } else if map_x < world.width && map_y < world.height {
                     let mut found_item = false;
+                    // 平时只显示视野半径内的物品，雷达生效时在雷达半径内的物品会被高亮标出
+                    let in_sight = dist_sq < 100;
+                    let on_radar = radar_radius.is_some_and(|radius| (dist_sq as f64) <= radius * radius);
+                    let at_cell = |x: f64, y: f64| (x as usize) == map_x && (y as usize) == map_y;
+                    let visible_items = if in_sight || on_radar {
+                        items.iter().filter(|item| !item.collected && at_cell(item.x, item.y)).count()
+                    } else {
+                        0
+                    };
+                    let npcs_here = npcs.iter().filter(|npc| at_cell(npc.x, npc.y)).count();
+                    if visible_items + npcs_here > 1 {
+                        // 同一格有多个物体时用 * 表示，避免互相覆盖
+                        spans.push(Span::styled("*", Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
+                        found_item = true;
+                    }
                     for item in items {
+                        if found_item {
+                            break;
+                        }
+                        if !(in_sight || on_radar) {
+                            break;
+                        }
                         if !item.collected && (item.x as usize) == map_x && (item.y as usize) == map_y {
-                            let icon = match item.item_type {
-                                crate::entities::ItemType::Coin => '◆',
-                                crate::entities::ItemType::Key => '🔑',
-                                crate::entities::ItemType::Health => '❤',
-                                crate::entities::ItemType::Exit => '🚪',
-                            };
-                            let color = match item.item_type {
-                                crate::entities::ItemType::Coin => Color::Yellow,
-                                crate::entities::ItemType::Key => Color::Cyan,
-                                crate::entities::ItemType::Health => Color::Red,
-                                crate::entities::ItemType::Exit => Color::Green,
+                            let icon = Self::item_glyph(item.item_type);
+                            let color = self.color_mode.apply(self.item_color(item.item_type));
+                            let style = if on_radar {
+                                Style::default().fg(color).add_modifier(Modifier::BOLD | Modifier::REVERSED)
+                            } else {
+                                Style::default().fg(color)
                             };
-                            spans.push(Span::styled(icon.to_string(), Style::default().fg(color)));
+                            spans.push(Span::styled(self.display_char(icon).to_string(), style));
                             found_item = true;
                             break;
                         }
@@ -432,14 +810,8 @@ impl Renderer {
                     if !found_item {
                         for npc in npcs {
                             if (npc.x as usize) == map_x && (npc.y as usize) == map_y {
-                                let icon = match npc.npc_type {
-                                    crate::entities::NPCType::Wanderer => "T^T",
-                                    crate::entities::NPCType::Guard => "(^.^)",
-                                };
-                                let color = match npc.npc_type {
-                                    crate::entities::NPCType::Wanderer => Color::LightGreen,
-                                    crate::entities::NPCType::Guard => Color::LightRed,
-                                };
+                                let icon = Self::npc_glyph(npc.npc_type, npc.alert);
+                                let color = self.color_mode.apply(self.npc_color(npc.npc_type, npc.alert));
                                 spans.push(Span::styled(icon.to_string(), Style::default().fg(color)));
                                 found_item = true;
                                 break;
@@ -448,24 +820,19 @@ impl Renderer {
                     }
                     
                     if !found_item {
-                        if map[map_x][map_y] != WallType::Empty {
+                        let wall = map[map_x][map_y];
+                        if wall != WallType::Empty {
                             let wall_color = if monochrome_mode {
                                 Color::White
                             } else {
-                                match map[map_x][map_y] {
-                                    WallType::Red => Color::Red,
-                                    WallType::Green => Color::Green,
-                                    WallType::Blue => Color::Blue,
-                                    WallType::White => Color::White,
-                                    WallType::Yellow => Color::Yellow,
-                                    _ => Color::Gray,
-                                }
+                                let (r, g, b) = self.wall_rgb(wall);
+                                self.color_mode.apply(Color::Rgb(r, g, b))
                             };
-                            spans.push(Span::styled("█", Style::default().fg(wall_color)));
+                            spans.push(Span::styled(wall.minimap_glyph().to_string(), Style::default().fg(wall_color)));
                         } else {
-                            let is_visited = dist_sq < 100;
+                            let is_visited = visited.get(map_x).and_then(|column| column.get(map_y)).copied().unwrap_or(false);
                             if is_visited {
-                                spans.push(Span::styled("·", Style::default().fg(Color::DarkGray)));
+                                spans.push(Span::styled(self.display_char('·').to_string(), Style::default().fg(Color::DarkGray)));
                             } else {
                                 spans.push(Span::styled(" ", Style::default()));
                             }
@@ -483,9 +850,343 @@ impl Renderer {
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .title(vec![
-                    Span::styled("🗺️ ", Style::default().fg(Color::Yellow)),
+                    Span::styled(self.display_text("🗺️ "), Style::default().fg(Color::Yellow)),
                     Span::styled("Minimap", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
                 ]));
         frame.render_widget(minimap, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze_gen::{MAP_HEIGHT, MAP_WIDTH};
+    use crate::vec2::Vec2;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    /// 沿 x 方向的一条走廊：y = 1，x 从 1 到 len，其余都是红墙
+    fn corridor(len: usize) -> World {
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for column in map.iter_mut().skip(1).take(len) {
+            column[1] = WallType::Empty;
+        }
+        World::from_map(map, (1.5, 1.5))
+    }
+
+    /// 渲染一帧并把每一行拼成字符串
+    fn frame(renderer: &mut Renderer, camera: &Camera, world: &World, items: &[Item], npcs: &[NPC], size: (usize, usize)) -> Vec<String> {
+        let ctx = RenderContext { camera, world, items, npcs, monochrome_mode: false };
+        renderer
+            .render_to_buffer(size.0, size.1, &ctx)
+            .iter()
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+
+    /// 用 TestBackend 画出小地图，返回每一行的文字
+    fn minimap(renderer: &Renderer, ctx: &RenderContext, radar_radius: Option<f64>) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(26, 26)).unwrap();
+        terminal
+            .draw(|f| renderer.render_minimap(f, f.area(), ctx, &[], radar_radius))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn golden_frame_looking_down_a_corridor() {
+        let world = corridor(4);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let rows = frame(&mut Renderer::new(), &camera, &world, &[], &[], (24, 10));
+        let expected = [
+            "░░░                   ░░",
+            "░░░░░               ░░░░",
+            "▓▓▓░░░░           ░░░░▓▓",
+            "▓▓▓▓▓▓░░░       ░░░▓▓▓▓▓",
+            "▓▓▓▓▓▓░▓▓░═════░▓▓░▓▓▓▓▓",
+            "░░░░░░░░░░░░░░░░░░░░░░░░",
+            "░░░░░▓░▓▓▓▒▒▓▓▒▒▓▓░▓░░░░",
+            "▓▓▓▓▓▓░▒▓▓▒▒▓▓▒▒▓▓░▓▓▓▓▓",
+            "▓▓▓▓▓▒▓▓▒▒▓▓▒▒▓▓▒▒▓▓▓▓▓▓",
+            "▓▓▓▓▒▒▓▓▒▒▓▓▒▒▓▓▒▒▓▓▒▒▓▓",
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn pitch_moves_the_floor_and_ceiling_split() {
+        let (level_ceiling, level_floor) = Renderer::horizon_bands(30, 0);
+        let (up_ceiling, up_floor) = Renderer::horizon_bands(30, 5);
+        assert_eq!(up_ceiling, level_ceiling + 5);
+        assert_eq!(up_floor, level_floor + 5);
+
+        // 偏移再大，天花板和地板也都至少留一行
+        let (ceiling_end, floor_start) = Renderer::horizon_bands(30, 100);
+        assert!(ceiling_end >= 1);
+        assert!(floor_start <= 29);
+        let (ceiling_end, floor_start) = Renderer::horizon_bands(30, -100);
+        assert!(ceiling_end >= 1);
+        assert!(floor_start <= 29);
+    }
+
+    #[test]
+    fn smooth_texture_has_no_mortar_lines() {
+        let mut renderer = Renderer::new();
+        let samples: Vec<(f64, f64)> = (0..20)
+            .flat_map(|i| (0..20).map(move |j| (i as f64 / 20.0, j as f64 / 20.0)))
+            .collect();
+
+        renderer.wall_texture = WallTexture::Brick;
+        assert!(samples.iter().any(|&(x, y)| renderer.get_char(1.0, false, x, y) == '░'));
+
+        renderer.wall_texture = WallTexture::Smooth;
+        for &(x, y) in &samples {
+            assert!(!matches!(renderer.get_char(1.0, false, x, y), '░' | '═'));
+        }
+    }
+
+    #[test]
+    fn radar_reveals_items_beyond_the_usual_minimap_range() {
+        let world = corridor(20);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let items = [Item::new(13.5, 1.5, ItemType::Coin)];
+        let ctx = RenderContext { camera: &camera, world: &world, items: &items, npcs: &[], monochrome_mode: false };
+        let renderer = Renderer::new();
+
+        let has_coin = |rows: &[String]| rows.iter().any(|row| row.contains('◆'));
+        assert!(!has_coin(&minimap(&renderer, &ctx, None)));
+        assert!(has_coin(&minimap(&renderer, &ctx, Some(15.0))));
+    }
+
+    #[test]
+    fn far_wall_at_the_end_of_a_long_corridor_is_drawn() {
+        // 走廊贯穿整张地图，尽头的墙在 x = MAP_WIDTH - 1；宽度为偶数时中间一列正对前方
+        let world = corridor(MAP_WIDTH - 2);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let mut renderer = Renderer::new();
+        let rows = frame(&mut renderer, &camera, &world, &[], &[], (40, 200));
+
+        let center = 20;
+        let expected = (MAP_WIDTH - 1) as f64 - 1.5;
+        assert!((renderer.depth_buffer[center] - expected).abs() < 1e-6);
+        let horizon = &rows[100].chars().nth(center).unwrap();
+        assert!(!horizon.is_whitespace(), "no wall at the horizon: {:?}", horizon);
+    }
+
+    #[test]
+    fn near_items_sit_on_the_floor_and_far_ones_near_the_horizon() {
+        let world = corridor(20);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let coin_row = |distance: f64| {
+            let items = [Item::new(1.5 + distance, 1.5, ItemType::Coin)];
+            let rows = frame(&mut Renderer::new(), &camera, &world, &items, &[], (40, 30));
+            let row = rows.iter().position(|row| row.contains('◆')).expect("coin not drawn");
+            (row, rows)
+        };
+
+        let (near, rows) = coin_row(2.0);
+        assert!(near > 15 + 2, "near coin at row {}", near);
+        assert!(rows[near + 1].contains('▁'), "no shadow under the near coin");
+
+        let (far, rows) = coin_row(16.0);
+        assert!(far.abs_diff(15) <= 1, "far coin at row {}", far);
+        assert!(!rows.iter().any(|row| row.contains('▁')));
+    }
+
+    #[test]
+    fn two_items_on_one_cell_show_the_multiple_marker() {
+        let world = corridor(20);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let renderer = Renderer::new();
+
+        let single = [Item::new(5.5, 1.5, ItemType::Coin)];
+        let ctx = RenderContext { camera: &camera, world: &world, items: &single, npcs: &[], monochrome_mode: false };
+        let rows = minimap(&renderer, &ctx, None);
+        assert!(rows[2].contains('◆') && !rows[2].contains('*'), "{:?}", rows[2]);
+
+        let stacked = [Item::new(5.2, 1.2, ItemType::Coin), Item::new(5.8, 1.8, ItemType::Coin)];
+        let ctx = RenderContext { items: &stacked, ..ctx };
+        let rows = minimap(&renderer, &ctx, None);
+        assert!(rows[2].contains('*') && !rows[2].contains('◆'), "{:?}", rows[2]);
+    }
+
+    #[test]
+    fn stars_stay_put_between_frames() {
+        let mut renderer = Renderer::new();
+        renderer.ceiling_style = CeilingStyle::Stars;
+        renderer.clear(80, 30, 0);
+        let first = renderer.buffer.clone();
+        renderer.clear(80, 30, 0);
+        assert_eq!(renderer.buffer, first);
+
+        let (ceiling_end, _) = Renderer::horizon_bands(30, 0);
+        let stars = first[..ceiling_end].iter().flatten().filter(|&&ch| ch == '*' || ch == '·').count();
+        assert!(stars > 0);
+    }
+
+    #[test]
+    fn rear_view_shows_the_wall_behind_the_player() {
+        let world = corridor(10);
+        // 面朝 +x，身后 2.5 格是 x = 0 的墙，前方的墙在 7.5 格外
+        let camera = Camera::new(Vec2::new(3.5, 1.5), Vec2::new(1.0, 0.0));
+        let ctx = RenderContext { camera: &camera, world: &world, items: &[], npcs: &[], monochrome_mode: false };
+        let mut renderer = Renderer::new();
+        let mut terminal = Terminal::new(TestBackend::new(80, 40)).unwrap();
+        terminal.draw(|f| renderer.render_rear_view(f, f.area(), &ctx)).unwrap();
+
+        // 插图内部 18x8，中间一列正对身后
+        let (center, middle) = (9, 4);
+        assert!((renderer.depth_buffer[center] - 2.5).abs() < 1e-6);
+        let wall = renderer.buffer[middle][center];
+        assert!(!wall.is_whitespace() && wall != '·', "center column shows {:?}", wall);
+
+        let buffer = terminal.backend().buffer();
+        let inset_left = 80 - 20 - 1;
+        assert_eq!(buffer[(inset_left + 1 + center as u16, 2 + middle as u16)].symbol(), wall.to_string());
+    }
+
+    #[test]
+    fn edge_glyph_uses_half_blocks_for_partial_rows() {
+        // 墙覆盖半行 5..11：第 2 行只有下半、第 5 行只有上半
+        assert_eq!(edge_glyph(5, 11, 1), None);
+        assert_eq!(edge_glyph(5, 11, 2), Some('▄'));
+        assert_eq!(edge_glyph(5, 11, 3), None);
+        assert_eq!(edge_glyph(5, 11, 5), Some('▀'));
+        assert_eq!(edge_glyph(5, 11, 6), None);
+        // 边界正好落在整行上时不需要半块
+        assert_eq!(edge_glyph(4, 12, 2), None);
+        assert_eq!(edge_glyph(4, 12, 5), None);
+    }
+
+    #[test]
+    fn smooth_edges_draw_half_blocks_at_the_wall_ends() {
+        let world = corridor(4);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let mut renderer = Renderer::new();
+        let plain = frame(&mut renderer, &camera, &world, &[], &[], (24, 15));
+        assert!(!plain.concat().contains(['▀', '▄']));
+
+        renderer.smooth_edges = true;
+        let smooth = frame(&mut renderer, &camera, &world, &[], &[], (24, 15));
+        assert!(smooth.concat().contains(['▀', '▄']));
+    }
+
+    #[test]
+    fn pitching_moves_floor_sprites_with_the_walls() {
+        let world = corridor(6);
+        let items = [Item::new(3.5, 1.5, ItemType::Coin)];
+        // 返回 (水平偏移, 金币所在行, 尽头那面墙底边所在行)
+        let rows_at = |pitch: f64| {
+            let mut camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+            camera.pitch = pitch;
+            let rows = frame(&mut Renderer::new(), &camera, &world, &items, &[], (40, 40));
+            let coin = rows.iter().position(|row| row.contains('◆')).expect("coin not drawn") as i32;
+            let base = rows.iter().position(|row| row.contains("···········")).expect("wall base not drawn") as i32;
+            (camera.get_horizon_offset(), coin, base)
+        };
+
+        let (_, level_coin, level_base) = rows_at(0.0);
+        for pitch in [0.05, -0.05] {
+            let (offset, coin, base) = rows_at(pitch);
+            assert_ne!(offset, 0);
+            assert_eq!(base - level_base, offset, "wall moved {} for offset {}", base - level_base, offset);
+            assert_eq!(coin - level_coin, offset, "coin moved {} for offset {}", coin - level_coin, offset);
+        }
+    }
+
+    #[test]
+    fn ascii_only_frames_contain_only_ascii() {
+        let world = corridor(6);
+        let camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+        let items = [Item::new(3.5, 1.5, ItemType::Coin), Item::new(4.5, 1.5, ItemType::Key)];
+        let npcs = [NPC::new(5.5, 1.5, NPCType::Wanderer)];
+
+        let mut renderer = Renderer::new();
+        let unicode = frame(&mut renderer, &camera, &world, &items, &npcs, (40, 20)).concat();
+        assert!(!unicode.is_ascii());
+
+        renderer.ascii_only = true;
+        let ascii = frame(&mut renderer, &camera, &world, &items, &npcs, (40, 20)).concat();
+        assert!(ascii.is_ascii(), "non-ASCII output: {:?}", ascii.chars().filter(|ch| !ch.is_ascii()).collect::<String>());
+        assert!(ascii.contains(['@', '#', '*', '+']), "walls missing");
+        assert!(ascii.contains('$'), "coin missing");
+
+        assert_eq!(renderer.display_text("🎮 3D VIEW "), "3D VIEW ");
+        assert_eq!(renderer.display_text("⚠ Close "), "! Close ");
+    }
+
+    #[test]
+    fn minimap_legend_snapshot() {
+        let text = |renderer: &Renderer| -> Vec<String> {
+            renderer
+                .minimap_legend_lines(false)
+                .iter()
+                .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect())
+                .collect()
+        };
+        let expected = [
+            "→     You (facing)",
+            "◉     Around you",
+            "·     Visited",
+            "*     Several things",
+            "◆     Coin",
+            "🔑     Key",
+            "❤     Health",
+            "🚪     Exit",
+            "📡     Radar",
+            "🧲     Magnet",
+            "T^T   Wanderer",
+            "(^.^) Guard",
+            "(!.!) Guard (alert)",
+            "#     Red wall",
+            "=     Green wall",
+            "%     Blue wall",
+            "@     White wall",
+            "&     Yellow wall",
+            "+     Door",
+            "!     Switch",
+            "X     Border wall",
+            "\"     Glass wall",
+        ];
+        assert_eq!(text(&Renderer::new()), expected);
+
+        // ASCII 模式下图例用的字符和小地图一样跟着替换
+        let mut renderer = Renderer::new();
+        renderer.ascii_only = true;
+        let ascii = text(&renderer);
+        assert!(ascii.iter().all(|line| line.is_ascii()), "{:?}", ascii);
+        assert_eq!(ascii[0], ">     You (facing)");
+        assert_eq!(ascii[4], "$     Coin");
+    }
+
+    #[test]
+    fn glass_is_walked_through_but_still_drawn() {
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for column in map.iter_mut().skip(1).take(6) {
+            column[1] = WallType::Empty;
+        }
+        map[3][1] = WallType::Glass;
+        let world = World::from_map(map, (1.5, 1.5));
+        let mut camera = Camera::new(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0));
+
+        // 视线停在玻璃上，而不是走廊尽头
+        let mut renderer = Renderer::new();
+        let rows = frame(&mut renderer, &camera, &world, &[], &[], (40, 20));
+        assert!((renderer.depth_buffer[20] - 1.5).abs() < 0.1, "depth {}", renderer.depth_buffer[20]);
+        assert!(rows.iter().any(|row| row.chars().nth(20).is_some_and(|ch| ch != ' ')));
+
+        for _ in 0..20 {
+            camera.move_forward(&world, 1.0);
+        }
+        assert!(camera.position.x > 4.0, "stopped at {:?}", camera.position);
+
+        // 穿过去以后回头还能看到玻璃
+        let camera = Camera::new(camera.position, Vec2::new(-1.0, 0.0));
+        frame(&mut renderer, &camera, &world, &[], &[], (40, 20));
+        let expected = camera.position.x - 4.0;
+        assert!((renderer.depth_buffer[20] - expected).abs() < 0.1, "depth {} vs {}", renderer.depth_buffer[20], expected);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{Args, Button, Difficulty};
+use clap::{Parser, ValueEnum};
+use std::fs;
+use std::io;
+
+/// 会改变游戏状态的玩家操作；只有这些会被录下来，画面相关的开关不影响回放结果
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Button(Button),
+    Sprint(Button),
+    Rotate(f64),
+    LookUp(f64),
+    LookDown(f64),
+    Jump,
+    Interact,
+    NewMazeKeepProgress,
+    TogglePeaceful,
+    ToggleAutoWalk,
+    CancelAutoWalk,
+    ToggleNoclip,
+}
+
+/// 录像中的一条记录：推进一帧（带当时的帧间隔），或两帧之间发生的一次操作
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplayEntry {
+    Tick(f64),
+    Action(Action),
+}
+
+/// 一局游戏的录像。种子、难度和其他影响模拟的参数决定初始布局，之后按顺序重放每一帧和每个操作即可复现整局
+pub struct Recording {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub npc_safe_radius: f64,
+    pub explore_goal: f64,
+    pub entries: Vec<ReplayEntry>,
+}
+
+const BUTTON_NAMES: [(Button, &str); 8] = [
+    (Button::Forward, "forward"),
+    (Button::Backward, "backward"),
+    (Button::StrafeLeft, "strafe_left"),
+    (Button::StrafeRight, "strafe_right"),
+    (Button::RotateLeft, "rotate_left"),
+    (Button::RotateRight, "rotate_right"),
+    (Button::ResetView, "reset_view"),
+    (Button::NewMaze, "new_maze"),
+];
+
+fn button_name(button: Button) -> &'static str {
+    BUTTON_NAMES
+        .iter()
+        .find(|(b, _)| *b == button)
+        .map(|(_, name)| *name)
+        .unwrap_or("forward")
+}
+
+fn button_from_name(name: &str) -> Option<Button> {
+    BUTTON_NAMES.iter().find(|(_, n)| *n == name).map(|(b, _)| *b)
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid replay line: {}", line))
+}
+
+impl ReplayEntry {
+    // f64 的 Display 输出能精确还原，保证回放时帧间隔和录制时完全一致
+    fn encode(&self) -> String {
+        match self {
+            ReplayEntry::Tick(dt) => format!("tick {}", dt),
+            ReplayEntry::Action(action) => match action {
+                Action::Button(button) => format!("button {}", button_name(*button)),
+                Action::Sprint(button) => format!("sprint {}", button_name(*button)),
+                Action::Rotate(angle) => format!("rotate {}", angle),
+                Action::LookUp(amount) => format!("look_up {}", amount),
+                Action::LookDown(amount) => format!("look_down {}", amount),
+                Action::Jump => "jump".to_string(),
+                Action::Interact => "interact".to_string(),
+                Action::NewMazeKeepProgress => "new_maze_keep".to_string(),
+                Action::TogglePeaceful => "peaceful".to_string(),
+                Action::ToggleAutoWalk => "auto_walk".to_string(),
+                Action::CancelAutoWalk => "cancel_auto_walk".to_string(),
+                Action::ToggleNoclip => "noclip".to_string(),
+            },
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let (name, arg) = match line.split_once(' ') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (line, None),
+        };
+        let number = || arg.and_then(|arg| arg.parse::<f64>().ok());
+        let button = || arg.and_then(button_from_name);
+
+        let action = match name {
+            // 负数或非有限的帧间隔会让模拟出错（NPC 的随机概率越界），当作损坏的记录
+            "tick" => return number().filter(|dt| dt.is_finite() && *dt >= 0.0).map(ReplayEntry::Tick),
+            "button" => Action::Button(button()?),
+            "sprint" => Action::Sprint(button()?),
+            "rotate" => Action::Rotate(number()?),
+            "look_up" => Action::LookUp(number()?),
+            "look_down" => Action::LookDown(number()?),
+            "jump" => Action::Jump,
+            "interact" => Action::Interact,
+            "new_maze_keep" => Action::NewMazeKeepProgress,
+            "peaceful" => Action::TogglePeaceful,
+            "auto_walk" => Action::ToggleAutoWalk,
+            "cancel_auto_walk" => Action::CancelAutoWalk,
+            "noclip" => Action::ToggleNoclip,
+            _ => return None,
+        };
+        Some(ReplayEntry::Action(action))
+    }
+}
+
+impl Recording {
+    /// 记下命令行里所有会影响模拟的参数；颜色、ASCII 之类只影响画面的参数不需要
+    pub fn new(seed: u64, args: &Args) -> Self {
+        Recording {
+            seed,
+            difficulty: args.difficulty,
+            npc_safe_radius: args.npc_safe_radius,
+            explore_goal: args.explore_goal,
+            entries: Vec::new(),
+        }
+    }
+
+    /// 回放前把录制时的参数写回命令行参数
+    pub fn apply_to(&self, args: &mut Args) {
+        args.seed = Some(self.seed);
+        args.difficulty = self.difficulty;
+        args.npc_safe_radius = self.npc_safe_radius;
+        args.explore_goal = self.explore_goal;
+    }
+
+    /// 文本格式：开头几行是种子、难度和其他参数，之后每行一条记录
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let difficulty = self
+            .difficulty
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        let mut text = format!("seed {}\ndifficulty {}\n", self.seed, difficulty);
+        text.push_str(&format!("npc_safe_radius {}\nexplore_goal {}\n", self.npc_safe_radius, self.explore_goal));
+        for entry in &self.entries {
+            text.push_str(&entry.encode());
+            text.push('\n');
+        }
+        fs::write(path, text)
+    }
+
+    /// 旧录像没有种子和难度以外的参数行，这些参数按命令行默认值处理
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty()).peekable();
+
+        let seed_line = lines.next().unwrap_or_default();
+        let seed = seed_line
+            .strip_prefix("seed ")
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| invalid(seed_line))?;
+        let difficulty_line = lines.next().unwrap_or_default();
+        let difficulty = difficulty_line
+            .strip_prefix("difficulty ")
+            .and_then(|name| Difficulty::from_str(name, true).ok())
+            .ok_or_else(|| invalid(difficulty_line))?;
+
+        let defaults = Args::parse_from(["arsvt3d"]);
+        let (mut npc_safe_radius, mut explore_goal) = (defaults.npc_safe_radius, defaults.explore_goal);
+        while let Some(&line) = lines.peek() {
+            let Some((name, value)) = line.split_once(' ') else { break };
+            let setting = match name {
+                "npc_safe_radius" => &mut npc_safe_radius,
+                "explore_goal" => &mut explore_goal,
+                _ => break,
+            };
+            *setting = value.trim().parse().map_err(|_| invalid(line))?;
+            lines.next();
+        }
+
+        let entries = lines
+            .map(|line| ReplayEntry::decode(line.trim()).ok_or_else(|| invalid(line)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Recording {
+            seed,
+            difficulty,
+            npc_safe_radius,
+            explore_goal,
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("arsvt3d-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn save_and_load_round_trip_header_and_entries() {
+        let mut args = Args::parse_from(["arsvt3d", "--difficulty", "hard", "--npc-safe-radius", "6.5"]);
+        let mut recording = Recording::new(42, &args);
+        recording.entries = vec![
+            ReplayEntry::Tick(1.0 / 30.0),
+            ReplayEntry::Action(Action::Button(Button::StrafeLeft)),
+            ReplayEntry::Action(Action::Sprint(Button::Forward)),
+            ReplayEntry::Action(Action::Rotate(-0.123456789)),
+            ReplayEntry::Action(Action::LookUp(0.5)),
+            ReplayEntry::Action(Action::Jump),
+            ReplayEntry::Action(Action::ToggleNoclip),
+            ReplayEntry::Tick(0.016),
+        ];
+        let path = temp_path("round-trip.replay");
+        recording.save(path.to_str().unwrap()).unwrap();
+        let loaded = Recording::load(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.difficulty, Difficulty::Hard);
+        assert_eq!(loaded.npc_safe_radius, 6.5);
+        assert_eq!(loaded.explore_goal, recording.explore_goal);
+        assert_eq!(loaded.entries, recording.entries);
+
+        args.seed = None;
+        args.difficulty = Difficulty::Easy;
+        loaded.apply_to(&mut args);
+        assert_eq!(args.seed, Some(42));
+        assert_eq!(args.difficulty, Difficulty::Hard);
+    }
+
+    #[test]
+    fn old_recordings_without_settings_use_defaults_and_bad_lines_fail() {
+        let path = temp_path("old.replay");
+        fs::write(&path, "seed 3\ndifficulty easy\ntick 0.5\njump\n").unwrap();
+        let loaded = Recording::load(path.to_str().unwrap()).unwrap();
+        let defaults = Args::parse_from(["arsvt3d"]);
+        assert_eq!(loaded.npc_safe_radius, defaults.npc_safe_radius);
+        assert_eq!(loaded.entries, vec![ReplayEntry::Tick(0.5), ReplayEntry::Action(Action::Jump)]);
+
+        for bad in ["button sideways", "tick -0.5", "tick NaN", "tick inf"] {
+            fs::write(&path, format!("seed 3\ndifficulty easy\n{}\n", bad)).unwrap();
+            let err = Recording::load(path.to_str().unwrap()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?} was accepted", bad);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_HEALTH: f64 = 100.0;
+
+// 每种收集物计入的分数
+const COIN_SCORE: u32 = 10;
+const KEY_SCORE: u32 = 50;
+
+/// 玩家的收集、生命和计分状态。计数一律饱和相加，生命值限制在 0..=MAX_HEALTH
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub coins: u32,
+    /// 手上的钥匙，开门会消耗
+    pub keys: u32,
+    /// 本迷宫里捡到过的钥匙数，出口按这个计数判断，开门不会减少
+    pub keys_found: u32,
+    pub steps: u32,
+    pub health: f64,
+    pub score: u32,
+    /// 本局已进行的时间（秒）
+    pub elapsed: f64,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        PlayerStats {
+            coins: 0,
+            keys: 0,
+            keys_found: 0,
+            steps: 0,
+            health: MAX_HEALTH,
+            score: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl PlayerStats {
+    pub fn collect_coin(&mut self) {
+        self.collect_coins(1);
+    }
+
+    pub fn collect_coins(&mut self, count: u32) {
+        self.coins = self.coins.saturating_add(count);
+        self.score = self.score.saturating_add(count.saturating_mul(COIN_SCORE));
+    }
+
+    pub fn collect_key(&mut self) {
+        self.keys = self.keys.saturating_add(1);
+        self.keys_found = self.keys_found.saturating_add(1);
+        self.score = self.score.saturating_add(KEY_SCORE);
+    }
+
+    pub fn take_damage(&mut self, amount: f64) {
+        self.health = (self.health - amount).clamp(0.0, MAX_HEALTH);
+    }
+
+    pub fn heal(&mut self, amount: f64) {
+        self.health = (self.health + amount).clamp(0.0, MAX_HEALTH);
+    }
+
+    pub fn step(&mut self) {
+        self.steps = self.steps.saturating_add(1);
+    }
+
+    pub fn advance(&mut self, delta_time: f64) {
+        self.elapsed += delta_time.max(0.0);
+    }
+
+    /// 换新迷宫时调用：步数和本迷宫的钥匙计数清零；不保留进度时金币、钥匙、分数和时间也清零。
+    /// 生命值总是保留
+    pub fn start_maze(&mut self, keep_progress: bool) {
+        self.steps = 0;
+        self.keys_found = 0;
+        if !keep_progress {
+            self.coins = 0;
+            self.keys = 0;
+            self.score = 0;
+            self.elapsed = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_stays_within_bounds() {
+        let mut stats = PlayerStats::default();
+        stats.heal(30.0);
+        assert_eq!(stats.health, MAX_HEALTH);
+        stats.take_damage(35.0);
+        assert_eq!(stats.health, 65.0);
+        stats.take_damage(500.0);
+        assert_eq!(stats.health, 0.0);
+        stats.heal(20.0);
+        assert_eq!(stats.health, 20.0);
+    }
+
+    #[test]
+    fn collecting_adds_score_and_saturates() {
+        let mut stats = PlayerStats::default();
+        stats.collect_coin();
+        stats.collect_key();
+        assert_eq!((stats.coins, stats.keys, stats.keys_found), (1, 1, 1));
+        assert_eq!(stats.score, COIN_SCORE + KEY_SCORE);
+
+        stats.coins = u32::MAX - 1;
+        stats.collect_coins(5);
+        assert_eq!(stats.coins, u32::MAX);
+        assert_eq!(stats.score, COIN_SCORE * 6 + KEY_SCORE);
+        stats.score = u32::MAX;
+        stats.steps = u32::MAX;
+        stats.collect_key();
+        stats.step();
+        assert_eq!((stats.score, stats.steps), (u32::MAX, u32::MAX));
+
+        stats.advance(1.5);
+        stats.advance(-3.0);
+        assert_eq!(stats.elapsed, 1.5);
+    }
+
+    #[test]
+    fn start_maze_resets_per_maze_counters() {
+        let mut stats = PlayerStats::default();
+        stats.collect_coin();
+        stats.collect_key();
+        stats.step();
+        stats.take_damage(10.0);
+        stats.advance(4.0);
+
+        let mut kept = stats;
+        kept.start_maze(true);
+        assert_eq!((kept.steps, kept.keys_found), (0, 0));
+        assert_eq!((kept.coins, kept.keys, kept.score, kept.elapsed), (1, 1, stats.score, 4.0));
+
+        stats.start_maze(false);
+        assert_eq!(stats, PlayerStats { health: 90.0, ..PlayerStats::default() });
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut stats = PlayerStats::default();
+        stats.collect_coins(3);
+        stats.take_damage(12.5);
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<PlayerStats>(&json).unwrap(), stats);
+    }
+}
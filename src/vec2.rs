@@ -1,6 +1,7 @@
-use std::ops::{Add, Sub, Mul, Div};
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: f64,
     pub y: f64,
@@ -37,6 +38,11 @@ impl Vec2 {
         self.x * other.x + self.y * other.y
     }
 
+    /// 与 x 轴正方向的夹角（弧度，范围 -PI..=PI）
+    pub fn angle(&self) -> f64 {
+        self.y.atan2(self.x)
+    }
+
     pub fn rotate(&self, angle: f64) -> Self {
         let cos = angle.cos();
         let sin = angle.sin();
@@ -90,3 +96,79 @@ impl Div<f64> for Vec2 {
         }
     }
 }
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
+}
+
+impl MulAssign<f64> for Vec2 {
+    fn mul_assign(&mut self, scalar: f64) {
+        self.x *= scalar;
+        self.y *= scalar;
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2 {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl From<(f64, f64)> for Vec2 {
+    fn from((x, y): (f64, f64)) -> Vec2 {
+        Vec2 { x, y }
+    }
+}
+
+impl From<Vec2> for (f64, f64) {
+    fn from(v: Vec2) -> (f64, f64) {
+        (v.x, v.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_and_from_tuples() {
+        let v: Vec2 = (1.5, -2.0).into();
+        assert_eq!(v, Vec2::new(1.5, -2.0));
+        let (x, y): (f64, f64) = v.into();
+        assert_eq!((x, y), (1.5, -2.0));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let v = Vec2::new(3.25, 0.5);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(serde_json::from_str::<Vec2>(&json).unwrap(), v);
+    }
+
+    #[test]
+    fn compound_assignment_and_negation() {
+        let mut v = Vec2::new(1.0, 2.0);
+        v += Vec2::new(0.5, -1.0);
+        assert_eq!(v, Vec2::new(1.5, 1.0));
+        v -= Vec2::new(1.0, 1.0);
+        assert_eq!(v, Vec2::new(0.5, 0.0));
+        v *= 4.0;
+        assert_eq!(v, Vec2::new(2.0, 0.0));
+        assert_eq!(-v, Vec2::new(-2.0, -0.0));
+    }
+}
@@ -1,4 +1,11 @@
 use crate::maze_gen::{MazeGenerator, MAP_WIDTH, MAP_HEIGHT};
+use crate::vec2::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+// 每个迷宫换成玻璃的墙数
+const GLASS_COUNT: usize = 6;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum WallType {
@@ -8,20 +15,158 @@ pub enum WallType {
     Blue = 3,
     White = 4,
     Yellow = 5,
+    Door = 6,
+    Switch = 7,
+    /// 迷宫最外圈的边界墙，和内部的墙区分开
+    Border = 8,
+    /// 玻璃墙：照常绘制，但可以穿过
+    Glass = 9,
 }
 
 impl WallType {
-    #[allow(dead_code)]
-    pub fn color(&self) -> u8 {
+    pub fn all() -> &'static [WallType] {
+        &[
+            WallType::Empty,
+            WallType::Red,
+            WallType::Green,
+            WallType::Blue,
+            WallType::White,
+            WallType::Yellow,
+            WallType::Door,
+            WallType::Switch,
+            WallType::Border,
+            WallType::Glass,
+        ]
+    }
+
+    pub fn base_rgb(&self) -> (u8, u8, u8) {
+        match self {
+            WallType::Empty => (128, 128, 128),
+            WallType::Red => (255, 80, 80),
+            WallType::Green => (80, 255, 80),
+            WallType::Blue => (100, 160, 255),
+            WallType::White => (255, 255, 255),
+            WallType::Yellow => (255, 255, 80),
+            WallType::Door => (160, 100, 50),
+            WallType::Switch => (255, 140, 0),
+            WallType::Border => (150, 110, 200),
+            WallType::Glass => (180, 230, 240),
+        }
+    }
+
+    /// 色盲友好配色（Okabe-Ito 调色板），红绿墙换成橙色和天蓝色
+    pub fn color_blind_rgb(&self) -> (u8, u8, u8) {
+        match self {
+            WallType::Empty => (128, 128, 128),
+            WallType::Red => (230, 159, 0),
+            WallType::Green => (86, 180, 233),
+            WallType::Blue => (0, 114, 178),
+            WallType::White => (255, 255, 255),
+            WallType::Yellow => (240, 228, 66),
+            WallType::Door => (160, 100, 50),
+            WallType::Switch => (213, 94, 0),
+            WallType::Border => (170, 170, 170),
+            WallType::Glass => (200, 235, 245),
+        }
+    }
+
+    /// 小地图上的字符，没有颜色时也能区分墙的种类
+    pub fn minimap_glyph(&self) -> char {
         match self {
-            WallType::Empty => 0,
-            WallType::Red => 1,
-            WallType::Green => 2,
-            WallType::Blue => 3,
-            WallType::White => 4,
-            WallType::Yellow => 5,
+            WallType::Empty => ' ',
+            WallType::Red => '#',
+            WallType::Green => '=',
+            WallType::Blue => '%',
+            WallType::White => '@',
+            WallType::Yellow => '&',
+            WallType::Door => '+',
+            WallType::Switch => '!',
+            WallType::Border => 'X',
+            WallType::Glass => '"',
         }
     }
+
+    /// 状态栏里显示的名字
+    pub fn name(&self) -> &'static str {
+        match self {
+            WallType::Empty => "Empty",
+            WallType::Red => "Red wall",
+            WallType::Green => "Green wall",
+            WallType::Blue => "Blue wall",
+            WallType::White => "White wall",
+            WallType::Yellow => "Yellow wall",
+            WallType::Door => "Door",
+            WallType::Switch => "Switch",
+            WallType::Border => "Border wall",
+            WallType::Glass => "Glass wall",
+        }
+    }
+
+    /// 是否阻挡移动。不阻挡的墙仍然会被射线命中并绘制出来
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, WallType::Empty | WallType::Glass)
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, WallType::Door | WallType::Switch)
+    }
+}
+
+/// 开关与它控制的那面墙
+#[derive(Clone, Copy, Debug)]
+pub struct SwitchLink {
+    pub switch: (usize, usize),
+    pub target: (usize, usize),
+    pub closed_type: WallType,
+}
+
+/// 每个格子的附加属性：陷阱会在踩上时扣血，亮度会乘到看向该格的墙面上
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileInfo {
+    pub is_trap: bool,
+    pub light_level: f64,
+}
+
+impl Default for TileInfo {
+    fn default() -> Self {
+        TileInfo {
+            is_trap: false,
+            light_level: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interaction {
+    Nothing,
+    DoorOpened,
+    DoorLocked,
+    SwitchToggled,
+}
+
+/// 迷宫结构统计：按每个空格相邻空格的数量分类
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MazeStats {
+    pub open_cells: usize,
+    /// 只有一个相邻空格的格子
+    pub dead_ends: usize,
+    /// 有三个及以上相邻空格的格子
+    pub junctions: usize,
+    /// 最长的一段水平或竖直直走廊（格数）
+    pub longest_corridor: usize,
+}
+
+/// 一条射线撞到的墙
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+    pub cell: (i32, i32),
+    pub wall_type: WallType,
+    /// 以射线方向向量的长度为单位的距离；渲染时方向不是单位向量，得到的正好是到相机平面的垂直距离
+    pub distance: f64,
+    /// true 表示撞到的是南北向的面（y 方向跨格）
+    pub side: bool,
+    /// 撞击点在墙面上的横向位置，0..1
+    pub wall_x: f64,
 }
 
 pub struct World {
@@ -29,19 +174,53 @@ pub struct World {
     pub width: usize,
     pub height: usize,
     start_pos: (f64, f64),
+    switch_links: Vec<SwitchLink>,
+    // 格子属性表，没有设置过任何属性时为 None，所有格子都取默认值
+    tiles: Option<Vec<Vec<TileInfo>>>,
 }
 
 impl World {
     pub fn new_random() -> Self {
+        Self::generate(&mut rand::thread_rng())
+    }
+
+    /// 相同的种子总是生成相同的迷宫、起点和机关
+    pub fn from_seed(seed: u64) -> Self {
+        Self::generate(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// 用调用方的随机数生成器生成迷宫，同一个生成器状态得到同一个迷宫
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
+        Self::generate(rng)
+    }
+
+    fn generate(rng: &mut impl Rng) -> Self {
         let mut generator = MazeGenerator::new();
-        let map = generator.generate();
-        let start_pos = generator.get_start_position();
-        
-        World { 
+        let map = generator.generate(rng);
+
+        let mut world = World {
+            map,
+            width: MAP_WIDTH,
+            height: MAP_HEIGHT,
+            start_pos: (1.5, 1.5),
+            switch_links: Vec::new(),
+            tiles: None,
+        };
+        world.start_pos = MazeGenerator::get_start_position(&world, rng);
+        world.place_glass(GLASS_COUNT, rng);
+        world.place_interactives(2, 2, rng);
+        world
+    }
+
+    /// 用给定的地图构造世界，便于写出可复现的场景
+    pub fn from_map(map: [[WallType; MAP_HEIGHT]; MAP_WIDTH], start_pos: (f64, f64)) -> Self {
+        World {
             map,
             width: MAP_WIDTH,
             height: MAP_HEIGHT,
             start_pos,
+            switch_links: Vec::new(),
+            tiles: None,
         }
     }
 
@@ -51,16 +230,589 @@ impl World {
 
     pub fn get(&self, x: i32, y: i32) -> WallType {
         if x < 0 || y < 0 || x >= MAP_WIDTH as i32 || y >= MAP_HEIGHT as i32 {
-            return WallType::Red;
+            return WallType::Border;
         }
         self.map[x as usize][y as usize]
     }
 
+    /// 连续坐标所在格子的墙类型，向下取整，-0.5 落在 -1 格（地图外）而不是 0 格
+    pub fn cell_at(&self, pos: Vec2) -> WallType {
+        self.get(pos.x.floor() as i32, pos.y.floor() as i32)
+    }
+
+    pub fn is_wall_at(&self, pos: Vec2) -> bool {
+        self.cell_at(pos) != WallType::Empty
+    }
+
+    /// 连续坐标处是否有阻挡移动的墙，碰撞检测用它而不是 `is_wall_at`
+    pub fn is_solid_at(&self, pos: Vec2) -> bool {
+        self.cell_at(pos).is_solid()
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, wall_type: WallType) {
+        if x < 0 || y < 0 || x >= MAP_WIDTH as i32 || y >= MAP_HEIGHT as i32 {
+            return;
+        }
+        self.map[x as usize][y as usize] = wall_type;
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> TileInfo {
+        if x < 0 || y < 0 {
+            return TileInfo::default();
+        }
+        self.tiles
+            .as_ref()
+            .and_then(|tiles| tiles.get(x as usize)?.get(y as usize).copied())
+            .unwrap_or_default()
+    }
+
+    pub fn set_tile(&mut self, x: i32, y: i32, info: TileInfo) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let (width, height) = (self.width, self.height);
+        let tiles = self
+            .tiles
+            .get_or_insert_with(|| vec![vec![TileInfo::default(); height]; width]);
+        tiles[x as usize][y as usize] = info;
+    }
+
     pub fn is_wall(&self, x: i32, y: i32) -> bool {
         self.get(x, y) != WallType::Empty
     }
-    
+
+    /// 两点之间的连线是否没有被实心墙挡住（按格子逐个遍历，不检查起点和终点所在格），玻璃不挡视线
+    pub fn line_of_sight(&self, from: (f64, f64), to: (f64, f64)) -> bool {
+        let dx = to.0 - from.0;
+        let dy = to.1 - from.1;
+
+        let mut map_x = from.0.floor() as i32;
+        let mut map_y = from.1.floor() as i32;
+        let end_x = to.0.floor() as i32;
+        let end_y = to.1.floor() as i32;
+
+        let delta_x = if dx.abs() < 1e-10 { 1e30 } else { (1.0 / dx).abs() };
+        let delta_y = if dy.abs() < 1e-10 { 1e30 } else { (1.0 / dy).abs() };
+
+        let (step_x, mut t_max_x) = if dx < 0.0 {
+            (-1, (from.0 - map_x as f64) * delta_x)
+        } else {
+            (1, (map_x as f64 + 1.0 - from.0) * delta_x)
+        };
+        let (step_y, mut t_max_y) = if dy < 0.0 {
+            (-1, (from.1 - map_y as f64) * delta_y)
+        } else {
+            (1, (map_y as f64 + 1.0 - from.1) * delta_y)
+        };
+
+        while (map_x, map_y) != (end_x, end_y) {
+            if t_max_x < t_max_y {
+                if t_max_x > 1.0 {
+                    break;
+                }
+                map_x += step_x;
+                t_max_x += delta_x;
+            } else {
+                if t_max_y > 1.0 {
+                    break;
+                }
+                map_y += step_y;
+                t_max_y += delta_y;
+            }
+
+            if (map_x, map_y) != (end_x, end_y) && self.get(map_x, map_y).is_solid() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// DDA 射线投射，返回第一面墙；穿出地图都没撞到墙，或起点、方向不是有限值时返回 None
+    pub fn cast_ray(&self, origin: Vec2, dir: Vec2) -> Option<RayHit> {
+        if ![origin.x, origin.y, dir.x, dir.y].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        // 和 cell_at 一样向下取整，负坐标落在地图外的格子
+        let mut map_x = origin.x.floor() as i32;
+        let mut map_y = origin.y.floor() as i32;
+
+        let delta_dist_x = if dir.x.abs() < 1e-10 { 1e30 } else { (1.0 / dir.x).abs() };
+        let delta_dist_y = if dir.y.abs() < 1e-10 { 1e30 } else { (1.0 / dir.y).abs() };
+
+        let (step_x, mut side_dist_x) = if dir.x < 0.0 {
+            (-1, (origin.x - map_x as f64) * delta_dist_x)
+        } else {
+            (1, (map_x as f64 + 1.0 - origin.x) * delta_dist_x)
+        };
+        let (step_y, mut side_dist_y) = if dir.y < 0.0 {
+            (-1, (origin.y - map_y as f64) * delta_dist_y)
+        } else {
+            (1, (map_y as f64 + 1.0 - origin.y) * delta_dist_y)
+        };
+
+        // 任意一条射线在穿出地图前最多经过 width + height 个格子
+        for _ in 0..self.width + self.height {
+            let side = if side_dist_x < side_dist_y {
+                side_dist_x += delta_dist_x;
+                map_x += step_x;
+                false
+            } else {
+                side_dist_y += delta_dist_y;
+                map_y += step_y;
+                true
+            };
+
+            if self.is_wall(map_x, map_y) {
+                let distance = if side { side_dist_y - delta_dist_y } else { side_dist_x - delta_dist_x };
+                let wall_x = if side {
+                    origin.x + distance * dir.x
+                } else {
+                    origin.y + distance * dir.y
+                };
+                return Some(RayHit {
+                    cell: (map_x, map_y),
+                    wall_type: self.get(map_x, map_y),
+                    distance,
+                    side,
+                    wall_x: wall_x - wall_x.floor(),
+                });
+            }
+        }
+        None
+    }
+
+    /// 已探索的空格占全部空格的比例，visited 按 [x][y] 索引
+    pub fn explored_fraction(&self, visited: &[Vec<bool>]) -> f64 {
+        let (mut open, mut explored) = (0usize, 0usize);
+        for (x, y) in self.open_cells() {
+            open += 1;
+            if visited.get(x).and_then(|column| column.get(y)).copied().unwrap_or(false) {
+                explored += 1;
+            }
+        }
+        if open == 0 {
+            return 0.0;
+        }
+        explored as f64 / open as f64
+    }
+
+    /// 按列遍历所有非墙的格子
+    pub fn open_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.width)
+            .flat_map(move |x| (0..self.height).map(move |y| (x, y)))
+            .filter(move |&(x, y)| self.map[x][y] == WallType::Empty)
+    }
+
+    /// 用 BFS 在可以穿过的格子之间找最短路径，结果包含起点和终点；走不通时返回 None
+    pub fn find_path(&self, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        let is_open = |(x, y): (usize, usize)| x < self.width && y < self.height && !self.map[x][y].is_solid();
+        if !is_open(from) || !is_open(to) {
+            return None;
+        }
+
+        let mut came_from = vec![vec![None; self.height]; self.width];
+        came_from[from.0][from.1] = Some(from);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == to {
+                break;
+            }
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let next = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                if is_open(next) && came_from[next.0][next.1].is_none() {
+                    came_from[next.0][next.1] = Some((x, y));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        came_from[to.0][to.1]?;
+        let mut path = vec![to];
+        let mut current = to;
+        while current != from {
+            current = came_from[current.0][current.1]?;
+            path.push(current);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn stats(&self) -> MazeStats {
+        let mut stats = MazeStats::default();
+        for (x, y) in self.open_cells() {
+            stats.open_cells += 1;
+            let neighbors = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .filter(|(dx, dy)| !self.is_wall(x as i32 + dx, y as i32 + dy))
+                .count();
+            match neighbors {
+                1 => stats.dead_ends += 1,
+                n if n >= 3 => stats.junctions += 1,
+                _ => {}
+            }
+        }
+
+        // 分别按行、按列统计连续空格的最长长度
+        let mut longest = 0;
+        for x in 0..self.width {
+            let mut run = 0;
+            for y in 0..self.height {
+                run = if self.map[x][y] == WallType::Empty { run + 1 } else { 0 };
+                longest = longest.max(run);
+            }
+        }
+        for y in 0..self.height {
+            let mut run = 0;
+            for x in 0..self.width {
+                run = if self.map[x][y] == WallType::Empty { run + 1 } else { 0 };
+                longest = longest.max(run);
+            }
+        }
+        stats.longest_corridor = longest;
+        stats
+    }
+
     pub fn get_map(&self) -> &[[WallType; MAP_HEIGHT]; MAP_WIDTH] {
         &self.map
     }
+
+    /// 与 (x, y) 处的格子交互：用一把钥匙打开门，或切换开关所连的墙
+    pub fn interact(&mut self, x: i32, y: i32, keys: &mut u32) -> Interaction {
+        match self.get(x, y) {
+            WallType::Door => {
+                if *keys == 0 {
+                    return Interaction::DoorLocked;
+                }
+                *keys -= 1;
+                self.set(x, y, WallType::Empty);
+                Interaction::DoorOpened
+            }
+            WallType::Switch => {
+                let link = self
+                    .switch_links
+                    .iter()
+                    .find(|link| link.switch == (x as usize, y as usize))
+                    .copied();
+                match link {
+                    Some(link) => {
+                        let (tx, ty) = (link.target.0 as i32, link.target.1 as i32);
+                        let toggled = if self.get(tx, ty) == WallType::Empty {
+                            link.closed_type
+                        } else {
+                            WallType::Empty
+                        };
+                        self.set(tx, ty, toggled);
+                        Interaction::SwitchToggled
+                    }
+                    None => Interaction::Nothing,
+                }
+            }
+            _ => Interaction::Nothing,
+        }
+    }
+
+    /// 从 from 出发、不穿过实心墙能走到的格子，按 [x][y] 索引
+    pub fn reachable_from(&self, from: (usize, usize)) -> Vec<Vec<bool>> {
+        let mut reachable = vec![vec![false; self.height]; self.width];
+        if from.0 >= self.width || from.1 >= self.height || self.map[from.0][from.1].is_solid() {
+            return reachable;
+        }
+        reachable[from.0][from.1] = true;
+        let mut queue = VecDeque::from([from]);
+        while let Some((x, y)) = queue.pop_front() {
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                if nx < self.width && ny < self.height && !reachable[nx][ny] && !self.map[nx][ny].is_solid() {
+                    reachable[nx][ny] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        reachable
+    }
+
+    /// 从起点出发能否走到所有可以穿过的格子
+    fn is_connected(&self) -> bool {
+        let start = (self.start_pos.0 as usize, self.start_pos.1 as usize);
+        let reachable = self.reachable_from(start);
+        (0..self.width).all(|x| (0..self.height).all(|y| self.map[x][y].is_solid() || reachable[x][y]))
+    }
+
+    fn is_interior(&self, x: usize, y: usize) -> bool {
+        x > 0 && y > 0 && x < self.width - 1 && y < self.height - 1
+    }
+
+    fn is_start_cell(&self, x: usize, y: usize) -> bool {
+        self.start_pos.0 as usize == x && self.start_pos.1 as usize == y
+    }
+
+    fn is_switch_target(&self, x: usize, y: usize) -> bool {
+        self.switch_links.iter().any(|link| link.target == (x, y))
+    }
+
+    /// 把几面隔开两条通道的内墙换成玻璃，玻璃两侧的通道因此连通
+    fn place_glass(&mut self, count: usize, rng: &mut impl Rng) {
+        let mut candidates = Vec::new();
+        for x in 2..self.width - 2 {
+            for y in 2..self.height - 2 {
+                if !self.map[x][y].is_solid() {
+                    continue;
+                }
+                let open = |dx: i32, dy: i32| self.get(x as i32 + dx, y as i32 + dy) == WallType::Empty;
+                if (open(-1, 0) && open(1, 0)) || (open(0, -1) && open(0, 1)) {
+                    candidates.push((x, y));
+                }
+            }
+        }
+        for _ in 0..count {
+            if candidates.is_empty() {
+                break;
+            }
+            let (x, y) = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+            self.map[x][y] = WallType::Glass;
+        }
+    }
+
+    fn place_interactives(&mut self, door_count: usize, switch_count: usize, rng: &mut impl Rng) {
+
+        // 门只放在直走廊上（两侧是墙、前后是路）
+        let mut corridors = Vec::new();
+        for x in 1..self.width - 1 {
+            for y in 1..self.height - 1 {
+                if self.map[x][y] != WallType::Empty || self.is_start_cell(x, y) {
+                    continue;
+                }
+                let open = |dx: i32, dy: i32| !self.is_wall(x as i32 + dx, y as i32 + dy);
+                let horizontal = open(-1, 0) && open(1, 0) && !open(0, -1) && !open(0, 1);
+                let vertical = open(0, -1) && open(0, 1) && !open(-1, 0) && !open(1, 0);
+                if horizontal || vertical {
+                    corridors.push((x, y));
+                }
+            }
+        }
+        // 只保留不会把迷宫隔开的门：不开门也能从起点走到每一个空格，钥匙和出口就不会被关在门后
+        let mut placed_doors = 0;
+        while placed_doors < door_count && !corridors.is_empty() {
+            let (x, y) = corridors.swap_remove(rng.gen_range(0..corridors.len()));
+            self.map[x][y] = WallType::Door;
+            if self.is_connected() {
+                placed_doors += 1;
+            } else {
+                self.map[x][y] = WallType::Empty;
+            }
+        }
+
+        // 开关控制一面隔开两条通道的墙，开关本身贴在其中一条通道旁的墙上
+        let mut candidates = Vec::new();
+        for x in 2..self.width - 2 {
+            for y in 2..self.height - 2 {
+                if !self.map[x][y].is_solid() || self.map[x][y].is_interactive() {
+                    continue;
+                }
+                let open = |dx: i32, dy: i32| self.get(x as i32 + dx, y as i32 + dy) == WallType::Empty;
+                if open(-1, 0) && open(1, 0) {
+                    candidates.push(((x, y), (x - 1, y)));
+                } else if open(0, -1) && open(0, 1) {
+                    candidates.push(((x, y), (x, y - 1)));
+                }
+            }
+        }
+        let mut placed = 0;
+        while placed < switch_count && !candidates.is_empty() {
+            let (target, approach) = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+            let target_type = self.map[target.0][target.1];
+            if !target_type.is_solid() || target_type.is_interactive() || self.is_switch_target(target.0, target.1) {
+                continue;
+            }
+            let (ax, ay) = (approach.0 as i32, approach.1 as i32);
+            let switch_cell = [(ax - 1, ay), (ax + 1, ay), (ax, ay - 1), (ax, ay + 1)]
+                .into_iter()
+                .filter(|&(sx, sy)| (sx as usize, sy as usize) != target && !self.is_switch_target(sx as usize, sy as usize))
+                .find(|&(sx, sy)| {
+                    let wall = self.get(sx, sy);
+                    wall.is_solid() && !wall.is_interactive() && self.is_interior(sx as usize, sy as usize)
+                });
+            if let Some((sx, sy)) = switch_cell {
+                self.switch_links.push(SwitchLink {
+                    switch: (sx as usize, sy as usize),
+                    target,
+                    closed_type: target_type,
+                });
+                self.map[sx as usize][sy as usize] = WallType::Switch;
+                placed += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 用字符画构造地图：'#' 是红墙，'D' 是门，'G' 是玻璃，'.' 是空地；画面之外全是墙
+    fn world_from_rows(rows: &[&str], start: (f64, f64)) -> World {
+        let mut map = [[WallType::Red; MAP_HEIGHT]; MAP_WIDTH];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                map[x][y] = match ch {
+                    'D' => WallType::Door,
+                    'G' => WallType::Glass,
+                    '.' => WallType::Empty,
+                    _ => WallType::Red,
+                };
+            }
+        }
+        World::from_map(map, start)
+    }
+
+    #[test]
+    fn door_opens_only_with_a_key() {
+        let mut world = world_from_rows(&["#####", "#.D.#", "#####"], (1.5, 1.5));
+        let mut keys = 0;
+        assert_eq!(world.interact(2, 1, &mut keys), Interaction::DoorLocked);
+        assert_eq!(world.get(2, 1), WallType::Door);
+
+        keys = 1;
+        assert_eq!(world.interact(2, 1, &mut keys), Interaction::DoorOpened);
+        assert_eq!(keys, 0);
+        assert_eq!(world.get(2, 1), WallType::Empty);
+    }
+
+    #[test]
+    fn generated_doors_never_cut_off_part_of_the_maze() {
+        for seed in 0..20 {
+            let world = World::from_seed(seed);
+            let start = world.get_start_position();
+            let reachable = world.reachable_from((start.0 as usize, start.1 as usize));
+            assert!(world.open_cells().all(|(x, y)| reachable[x][y]), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn open_cells_lists_only_empty_cells_column_by_column() {
+        let world = world_from_rows(&["#####", "#.D.#", "#G..#", "#####"], (1.5, 1.5));
+        let cells: Vec<_> = world.open_cells().collect();
+        assert_eq!(cells, vec![(1, 1), (2, 2), (3, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn find_path_runs_from_start_to_goal_one_step_at_a_time() {
+        let world = world_from_rows(&["######", "#....#", "#.##.#", "#....#", "######"], (1.5, 1.5));
+        let path = world.find_path((1, 1), (4, 3)).unwrap();
+        assert_eq!(path.first(), Some(&(1, 1)));
+        assert_eq!(path.last(), Some(&(4, 3)));
+        assert_eq!(path.len(), 6);
+        for step in path.windows(2) {
+            let (a, b) = (step[0], step[1]);
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+        }
+    }
+
+    #[test]
+    fn find_path_stops_at_locked_doors_but_crosses_glass() {
+        let world = world_from_rows(&["#######", "#.D.G.#", "#######"], (1.5, 1.5));
+        assert_eq!(world.find_path((1, 1), (3, 1)), None);
+        assert_eq!(world.find_path((3, 1), (5, 1)), Some(vec![(3, 1), (4, 1), (5, 1)]));
+        assert_eq!(world.find_path((1, 1), (1, 1)), Some(vec![(1, 1)]));
+    }
+
+    #[test]
+    fn every_wall_type_has_its_own_minimap_glyph() {
+        let glyphs: std::collections::HashSet<char> = WallType::all().iter().map(|wall| wall.minimap_glyph()).collect();
+        assert_eq!(glyphs.len(), WallType::all().len());
+        assert_eq!(WallType::Empty.minimap_glyph(), ' ');
+    }
+
+    #[test]
+    fn stats_of_a_tiny_t_shaped_maze() {
+        let world = world_from_rows(&["#######", "#.....#", "###.###", "###.###", "#######"], (1.5, 1.5));
+        assert_eq!(
+            world.stats(),
+            MazeStats { open_cells: 7, dead_ends: 3, junctions: 1, longest_corridor: 5 }
+        );
+    }
+
+    #[test]
+    fn cast_ray_reports_the_wall_it_hits() {
+        let mut world = world_from_rows(&["######", "#....#", "######"], (1.5, 1.5));
+        world.set(5, 1, WallType::Blue);
+
+        let hit = world.cast_ray(Vec2::new(1.5, 1.5), Vec2::new(1.0, 0.0)).unwrap();
+        assert_eq!(hit.cell, (5, 1));
+        assert_eq!(hit.wall_type, WallType::Blue);
+        assert!((hit.distance - 3.5).abs() < 1e-9);
+        assert!(!hit.side);
+        assert!((hit.wall_x - 0.5).abs() < 1e-9);
+
+        // 朝 +y 撞到的是水平方向的墙面
+        let hit = world.cast_ray(Vec2::new(2.25, 1.5), Vec2::new(0.0, 1.0)).unwrap();
+        assert_eq!(hit.cell, (2, 2));
+        assert!(hit.side);
+        assert!((hit.distance - 0.5).abs() < 1e-9);
+        assert!((hit.wall_x - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn explored_fraction_counts_visited_open_cells() {
+        let world = World::from_seed(3);
+        let open: Vec<_> = world.open_cells().collect();
+        let mut visited = vec![vec![false; world.height]; world.width];
+        assert_eq!(world.explored_fraction(&visited), 0.0);
+
+        for &(x, y) in open.iter().step_by(2) {
+            visited[x][y] = true;
+        }
+        // 墙上的标记不算
+        visited[0][0] = true;
+        assert!((world.explored_fraction(&visited) - 0.5).abs() < 0.01);
+
+        for &(x, y) in &open {
+            visited[x][y] = true;
+        }
+        assert_eq!(world.explored_fraction(&visited), 1.0);
+    }
+
+    /// 蓝-黄对立通道：红绿色盲仍然能分辨的方向
+    fn blue_yellow((r, g, b): (u8, u8, u8)) -> f64 {
+        b as f64 - (r as f64 + g as f64) / 2.0
+    }
+
+    #[test]
+    fn color_blind_palette_separates_red_and_green_walls() {
+        // 默认配色的红墙和绿墙只差在红绿通道上
+        let (red, green) = (WallType::Red.base_rgb(), WallType::Green.base_rgb());
+        assert!((blue_yellow(red) - blue_yellow(green)).abs() < 1.0);
+
+        let (red, green) = (WallType::Red.color_blind_rgb(), WallType::Green.color_blind_rgb());
+        assert!((blue_yellow(red) - blue_yellow(green)).abs() > 100.0);
+    }
+
+    #[test]
+    fn cell_at_floors_negative_coordinates() {
+        // 左上角那格是空地，`as i32` 会把 -0.5 截成 0 而误判为可走
+        let world = world_from_rows(&["..", ".G"], (0.5, 0.5));
+        let outside = Vec2::new(-0.5, 0.5);
+        assert_eq!(world.get(outside.x as i32, outside.y as i32), WallType::Empty);
+        assert_eq!(world.cell_at(outside), WallType::Border);
+        assert!(world.is_wall_at(outside));
+        assert!(world.is_solid_at(Vec2::new(0.5, -0.5)));
+
+        assert_eq!(world.cell_at(Vec2::new(0.99, 0.0)), WallType::Empty);
+        assert_eq!(world.cell_at(Vec2::new(1.5, 1.5)), WallType::Glass);
+        assert!(world.is_wall_at(Vec2::new(1.5, 1.5)));
+        assert!(!world.is_solid_at(Vec2::new(1.5, 1.5)));
+    }
+
+    #[test]
+    fn cast_ray_rejects_non_finite_input() {
+        let world = world_from_rows(&["#####", "#...#", "#####"], (1.5, 1.5));
+        let origin = Vec2::new(1.5, 1.5);
+        let east = Vec2::new(1.0, 0.0);
+        assert!(world.cast_ray(origin, east).is_some());
+        assert!(world.cast_ray(Vec2::new(f64::NAN, 1.5), east).is_none());
+        assert!(world.cast_ray(Vec2::new(1.5, f64::INFINITY), east).is_none());
+        assert!(world.cast_ray(origin, Vec2::new(f64::NAN, 0.0)).is_none());
+        assert!(world.cast_ray(origin, Vec2::new(1.0, f64::NEG_INFINITY)).is_none());
+    }
 }
@@ -0,0 +1,43 @@
+//! 运行 `--dump-state`，解析输出的 JSON 检查生成的关卡
+
+use std::process::Command;
+
+fn dump(seed: &str) -> serde_json::Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_arsvt3d"))
+        .args(["--dump-state", "--seed", seed])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn dump_state_describes_a_playable_level() {
+    let state = dump("5");
+    assert_eq!(state["seed"], 5);
+    let (width, height) = (state["width"].as_u64().unwrap() as usize, state["height"].as_u64().unwrap() as usize);
+    let map: Vec<Vec<char>> = state["map"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_str().unwrap().chars().collect())
+        .collect();
+    assert_eq!(map.len(), height);
+    assert!(map.iter().all(|row| row.len() == width));
+
+    let open = map.iter().flatten().filter(|&&ch| ch == ' ').count();
+    assert_eq!(state["open_cells"].as_u64().unwrap() as usize, open);
+
+    // 起点和所有物品都在空地上
+    let cell = |entity: &serde_json::Value| {
+        map[entity["y"].as_f64().unwrap() as usize][entity["x"].as_f64().unwrap() as usize]
+    };
+    assert_eq!(cell(&state["start"]), ' ');
+    assert!(!state["items"].as_array().unwrap().is_empty());
+    for item in state["items"].as_array().unwrap() {
+        assert_eq!(cell(item), ' ', "item {} is inside a wall", item);
+    }
+
+    // 同一个种子输出完全一样
+    assert_eq!(dump("5"), state);
+}
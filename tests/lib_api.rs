@@ -0,0 +1,27 @@
+//! 只通过 crate 根部导出的类型生成迷宫并渲染一帧，确认库可以脱离二进制使用
+
+use arsvt3d::{Camera, Item, ItemType, MazeGenerator, RenderContext, Renderer, Vec2, World, NPC, NPCType, MAP_HEIGHT, MAP_WIDTH};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[test]
+fn renders_a_frame_through_the_public_api() {
+    let world = World::from_seed(42);
+    let (x, y) = world.get_start_position();
+    let camera = Camera::new(Vec2::new(x, y), Vec2::new(1.0, 0.0));
+    let items = [Item::new(x + 1.0, y, ItemType::Coin)];
+    let npcs = [NPC::new(x, y + 1.0, NPCType::Wanderer)];
+    let ctx = RenderContext { camera: &camera, world: &world, items: &items, npcs: &npcs, monochrome_mode: true };
+
+    let mut renderer = Renderer::new();
+    let buffer = renderer.render_to_buffer(32, 12, &ctx);
+    assert_eq!(buffer.len(), 12);
+    assert!(buffer.iter().all(|row| row.len() == 32));
+}
+
+#[test]
+fn maze_generator_is_usable_on_its_own() {
+    let map = MazeGenerator::new().generate(&mut StdRng::seed_from_u64(1));
+    assert_eq!(map.len(), MAP_WIDTH);
+    assert_eq!(map[0].len(), MAP_HEIGHT);
+}